@@ -1,12 +1,13 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
-use std::{path::{Path, PathBuf}, time::Duration};
+use std::{collections::BTreeMap, path::{Path, PathBuf}, time::Duration};
 
+use alloy::primitives::{B256, U256};
 use automata_sgx_sdk::types::SgxStatus;
 use base::{eth::Eth, thread::parallel, trace::Alive};
 use clap::Parser;
-use scroll_da_codec::{BatchTask, Finalize};
+use scroll_da_codec::{v0, BlobSidecars, CommitBatchCall, FinalizeCall};
 use scroll_executor::{Address, BlockTrace};
 use scroll_verifier::{
     block_trace_to_pob, HardforkConfig, PobContext, ScrollBatchVerifier, ScrollExecutionNode,
@@ -23,6 +24,13 @@ struct Opt {
     private_key: String,
     #[clap(long)]
     registry_addr: Address,
+    /// ScrollChain on L1, the state roots bundles start from are read from it.
+    #[clap(long)]
+    scroll_chain: Option<Address>,
+    /// Blob sidecars archived from the beacon API (`*.json`), the traces of
+    /// v1+ batches are only checked against the committed blocks with them.
+    #[clap(long)]
+    blob_dir: Option<PathBuf>,
     #[clap(long, default_value = "60")]
     timeout_secs: usize,
     txs: Vec<PathBuf>,
@@ -39,7 +47,7 @@ pub extern "C" fn enclave_entrypoint() -> SgxStatus {
     SgxStatus::Success
 }
 
-fn read_batch_task(path: &PathBuf) -> BatchTask {
+fn read_commit_call(path: &PathBuf) -> CommitBatchCall {
     assert!(
         path.file_name()
             .unwrap()
@@ -52,17 +60,47 @@ fn read_batch_task(path: &PathBuf) -> BatchTask {
     let commit_tx_calldata = String::from_utf8_lossy(&commit_tx_calldata);
     let commit_tx_calldata = commit_tx_calldata.trim();
     let commit_tx_calldata = hex::decode(&commit_tx_calldata[2..]).unwrap();
-    let batch = BatchTask::from_calldata(&commit_tx_calldata[4..]).unwrap();
-    batch
+    CommitBatchCall::from_calldata(&commit_tx_calldata).unwrap()
 }
 
-fn read_finalize(path: &PathBuf) -> Finalize {
+fn read_finalize(path: &PathBuf) -> FinalizeCall {
     let file_name = path.file_name().unwrap().to_str().unwrap();
     let file_name = file_name.replace("commit", "finalize");
     let path = path.parent().unwrap().join(file_name);
     let calldata = std::fs::read(&path).map_err(|err| format!("read {:?}: {:?}", path, err)).unwrap();
     let calldata = hex::decode(&calldata[2..]).unwrap();
-    Finalize::from_calldata(&calldata[4..]).unwrap()
+    FinalizeCall::from_calldata(&calldata).unwrap()
+}
+
+alloy::sol! {
+    function finalizedStateRoots(uint256 batchIndex) external view returns (bytes32);
+}
+
+/// The state root a bundle starts from: the one of the parent batch if it was
+/// verified in this run, otherwise the one finalized on L1.
+async fn bundle_prev_state_root(
+    opt: &Opt,
+    l1: Option<&Eth>,
+    verified: &BTreeMap<B256, B256>,
+    finalize: &FinalizeCall,
+) -> Option<B256> {
+    let batch = finalize.batch();
+    if let Some(state_root) = verified.get(&batch.parent_batch_hash()) {
+        return Some(*state_root);
+    }
+    let scroll_chain = opt.scroll_chain?;
+    let l1 = l1?;
+    let call = finalizedStateRootsCall {
+        batchIndex: U256::from(batch.batch_index().checked_sub(1)?),
+    };
+    let state_root = match l1.call(scroll_chain, &call).await {
+        Ok(state_root) => state_root._0,
+        Err(err) => {
+            log::warn!("read finalized state root of batch {}: {:?}", call.batchIndex, err);
+            return None;
+        }
+    };
+    (state_root != B256::ZERO).then_some(state_root)
 }
 
 async fn run_verifier() {
@@ -78,6 +116,18 @@ async fn run_verifier() {
         dbg!(registration);
     }
 
+    let sidecars = opt
+        .blob_dir
+        .as_ref()
+        .map(|dir| BlobSidecars::from_dir(dir).unwrap());
+
+    // only needed for the state root a bundle starts from
+    let l1 = opt
+        .scroll_chain
+        .map(|_| Eth::dial(&opt.l1_endpoint, None).unwrap());
+
+    // batch hash => new state root of the batches verified so far
+    let mut verified = BTreeMap::new();
     for tx in &opt.txs {
         let file_stem = tx.file_stem().unwrap().to_str().unwrap();
         if !file_stem.contains("-commit-") {
@@ -86,8 +136,9 @@ async fn run_verifier() {
 
         log::info!("executing {}...", tx.display());
 
-        let batch = read_batch_task(tx);
-        let finalize = read_finalize(tx);
+        let call = read_commit_call(tx);
+        let batch = call.batch_task().unwrap();
+        let mut finalize = read_finalize(tx);
 
         let dir = tx.parent().unwrap().join("downloaded").join(file_stem);
 
@@ -166,11 +217,44 @@ async fn run_verifier() {
         log::info!("build batch header...");
         let new_batch = batch.build_batch(batch_version, &chunks).unwrap();
 
+        // the finalized header carries the blob versioned hash of the committed batch
+        let committed_batch = match finalize.batch() {
+            header if header.batch_index() == new_batch.batch_index() => header,
+            _ => &new_batch,
+        };
+        let blob = match &sidecars {
+            Some(sidecars) if call.version() != v0::VERSION => sidecars
+                .for_batch(committed_batch)
+                .map(|sidecar| sidecar.blob().unwrap())
+                .ok(),
+            _ => None,
+        };
+
         log::info!("executing blocks...");
-        let poe = ScrollBatchVerifier::verify(&batch, chunks).await.unwrap();
+        let poe = if call.version() == v0::VERSION || blob.is_some() {
+            ScrollBatchVerifier::verify_with_da(&call, blob.as_ref(), chunks)
+                .await
+                .unwrap()
+        } else {
+            log::warn!(
+                "no blob sidecar of batch {}, skip the DA check, try add --blob-dir=<dir>",
+                new_batch.batch_index()
+            );
+            ScrollBatchVerifier::verify(&batch, chunks).await.unwrap()
+        };
 
-        finalize.assert_poe(&poe);
-        dbg!(poe);
+        log::info!("poe: {:?}", poe);
+        if let FinalizeCall::Bundle(_) = finalize {
+            match bundle_prev_state_root(&opt, l1.as_ref(), &verified, &finalize).await {
+                Some(state_root) => finalize = finalize.with_prev_state_root(state_root),
+                None => log::warn!("unknown prev state root of the bundle, try add --scroll-chain=<address>"),
+            }
+        }
+        if let Err(mismatch) = finalize.check_poes(std::slice::from_ref(&poe)) {
+            log::error!("{}", mismatch);
+            continue;
+        }
+        verified.insert(poe.batch_hash, poe.new_state_root);
         
         log::info!("done");
     }
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use scroll_da_codec::BatchTask;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = BatchTask::from_calldata(data);
+});
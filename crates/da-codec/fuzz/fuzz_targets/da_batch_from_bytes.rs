@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use scroll_da_codec::DABatch;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(batch) = DABatch::from_bytes(data) {
+        assert_eq!(batch.encode(), data);
+    }
+});
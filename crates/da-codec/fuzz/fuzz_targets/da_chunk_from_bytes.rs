@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use scroll_da_codec::{v0, v1};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(chunk) = v0::DAChunk::from_bytes(data) {
+        assert_eq!(chunk.encode(), data);
+    }
+    if let Ok(chunk) = v1::DAChunk::from_bytes(data) {
+        assert_eq!(chunk.encode(), data);
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use scroll_da_codec::FinalizeCall;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = FinalizeCall::from_calldata(data);
+});
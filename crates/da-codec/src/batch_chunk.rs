@@ -1,73 +1,64 @@
-use base::eth::primitives::{keccak256, B256, U256};
+use base::eth::primitives::{B256, U256};
+use scroll_executor::{Context, Transaction};
 
-use super::BatchError;
+use super::{
+    v0, v1, BatchError, BlockHeaderInfo, BlockSlots, BlockTrait, ChunkTrait, RawTx, TxTrait,
+};
 
-#[derive(Clone, Debug)]
+/// A chunk of plain block data, hashed the same way as the chunk of `version`.
+/// Chunks built by BatchChunkBuilder::new keep the v0 hashing.
+#[derive(Clone, Debug, Default)]
 pub struct BatchChunk {
+    pub version: u8,
     pub blocks: Vec<BatchChunkBlock>,
 }
 
 impl BatchChunk {
-    pub fn encode(&self, mut total_l1_msg_poped_before: u64) -> Result<Vec<u8>, BatchError> {
-        let num_blocks = self.blocks.len();
-        if num_blocks > 255 || num_blocks == 0 {
-            return Err(BatchError::InvalidNumBlock(num_blocks));
-        }
+    pub fn encode(&self, total_l1_msg_poped_before: u64) -> Result<Vec<u8>, BatchError> {
+        self.to_v0_chunk().encode(total_l1_msg_poped_before)
+    }
 
-        let mut chunk_bytes = vec![num_blocks as u8];
-        let mut l2_tx_data_bytes = vec![];
-        for block in &self.blocks {
-            let block_bytes = block.encode(total_l1_msg_poped_before)?;
-            total_l1_msg_poped_before += block.num_l1_msg(total_l1_msg_poped_before);
-            if block_bytes.len() != 60 {
-                return Err(BatchError::InvalidBlockBytes(block_bytes.into()));
-            }
-            chunk_bytes.extend_from_slice(&block_bytes);
-            for tx in &block.txs {
-                if tx.l1_msg {
-                    continue;
-                }
-                let rlp_tx_data = &tx.encode;
-                l2_tx_data_bytes.extend_from_slice(&(rlp_tx_data.len() as u32).to_be_bytes());
-                l2_tx_data_bytes.extend(rlp_tx_data);
+    pub fn hash(&self, version: u8, total_l1_msg_poped_before: u64) -> Result<B256, BatchError> {
+        let chunk = self.to_v0_chunk();
+        match version {
+            v0::VERSION => chunk.hash(total_l1_msg_poped_before),
+            _ => v1::DAChunk {
+                blocks: chunk.blocks,
             }
+            .hash(total_l1_msg_poped_before),
         }
-
-        chunk_bytes.extend_from_slice(&l2_tx_data_bytes);
-        Ok(chunk_bytes)
     }
 
-    pub fn hash(&self, version: u8, total_l1_msg_poped_before: u64) -> Result<B256, BatchError> {
-        let chunk_bytes = self
-            .encode(total_l1_msg_poped_before)
-            .map_err(BatchError::EncodeBatchChunk())?;
-        let num_blocks = chunk_bytes[0] as usize;
-        let mut data_bytes = vec![];
-        for i in 0..num_blocks {
-            let start = 1 + 60 * i;
-            let end = 60 * i + 59;
-            data_bytes.extend_from_slice(&chunk_bytes[start..end]);
+    fn to_v0_chunk(&self) -> v0::DAChunk {
+        v0::DAChunk {
+            blocks: self.blocks.iter().cloned().map(Into::into).collect(),
         }
+    }
+}
+
+impl ChunkTrait for BatchChunk {
+    type Block = BatchChunkBlock;
 
+    fn add_block(&mut self, blk: Self::Block) {
+        self.blocks.push(blk);
+    }
+
+    fn blocks(&self) -> &[Self::Block] {
+        &self.blocks
+    }
+
+    fn num_l1_messages(&self, mut total_l1_message_popped_before: u64) -> u64 {
+        let mut num_l1_messages = 0_u64;
         for block in &self.blocks {
-            let mut l1_tx_hashes = vec![];
-            let mut l2_tx_hashes = vec![];
-            for tx in &block.txs {
-                if tx.l1_msg {
-                    l1_tx_hashes.extend_from_slice(tx.tx_hash.as_slice());
-                } else {
-                    if version == 0 {
-                        l2_tx_hashes.extend_from_slice(tx.tx_hash.as_slice());
-                    }
-                }
-            }
-            data_bytes.extend_from_slice(&l1_tx_hashes);
-            if version == 0 {
-                data_bytes.extend_from_slice(&l2_tx_hashes);
-            }
+            let num_l1_messages_in_block = block.num_l1_messages(total_l1_message_popped_before);
+            num_l1_messages += num_l1_messages_in_block;
+            total_l1_message_popped_before += num_l1_messages_in_block;
         }
+        num_l1_messages
+    }
 
-        Ok(keccak256(&data_bytes))
+    fn hash(&self, total_l1_msg_poped_before: u64) -> Result<B256, BatchError> {
+        BatchChunk::hash(self, self.version, total_l1_msg_poped_before)
     }
 }
 
@@ -83,51 +74,79 @@ pub struct BatchChunkBlock {
 
 impl BatchChunkBlock {
     pub fn num_l1_msg(&self, total_l1_msg_poped_before: u64) -> u64 {
-        let mut last_queue_index = None;
-        for tx in &self.txs {
-            if tx.l1_msg {
-                last_queue_index = Some(tx.nonce);
-            }
-        }
-        match last_queue_index {
-            Some(last_queue_index) => last_queue_index - total_l1_msg_poped_before + 1,
-            None => 0,
-        }
+        BlockTrait::num_l1_messages(self, total_l1_msg_poped_before)
     }
 
     pub fn num_l2_txs(&self) -> u64 {
-        let mut count = 0;
-        for tx in &self.txs {
-            if !tx.l1_msg {
-                count += 1;
-            }
-        }
-        return count;
+        self.txs.iter().filter(|tx| !tx.l1_msg).count() as u64
     }
 
     pub fn encode(&self, total_l1_msg_poped_before: u64) -> Result<Vec<u8>, BatchError> {
-        let mut bytes = Vec::with_capacity(60);
-        let num_l1_messages = self.num_l1_msg(total_l1_msg_poped_before);
-        if num_l1_messages > u16::max_value() as _ {
-            return Err(BatchError::NumL1TxTooLarge);
+        BlockTrait::encode(self, total_l1_msg_poped_before)
+    }
+}
+
+impl BlockTrait for BatchChunkBlock {
+    type Tx = BatchChunkBlockTx;
+
+    fn new<C: Context>(ctx: &C, txs: Vec<Self::Tx>) -> Self {
+        let blk = v0::DABlock::new(ctx, Vec::new());
+        Self {
+            number: blk.number,
+            timestamp: blk.timestamp,
+            base_fee: blk.base_fee,
+            gas_limit: blk.gas_limit,
+            hash: blk.hash,
+            txs,
         }
+    }
 
-        let num_l2_transactions = self.num_l2_txs();
-        let num_transactions = num_l1_messages + num_l2_transactions;
-        if num_transactions > u16::max_value() as _ {
-            return Err(BatchError::NumTxTooLarge);
+    fn from_header(header: &BlockHeaderInfo, txs: Vec<Self::Tx>) -> Self {
+        Self {
+            number: header.number,
+            timestamp: header.timestamp,
+            base_fee: header.base_fee,
+            gas_limit: header.gas_limit,
+            hash: B256::default(),
+            txs,
         }
-        bytes.extend_from_slice(&self.number.to_be_bytes());
-        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
-        let mut base_fee_bytes = [0_u8; 32];
-        if let Some(base_fee) = self.base_fee {
-            base_fee_bytes = base_fee.to_be_bytes();
+    }
+
+    fn number(&self) -> u64 {
+        self.number
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    fn txs(&self) -> &[Self::Tx] {
+        &self.txs
+    }
+
+    fn num_l1_messages(&self, total_l1_message_popped_before: u64) -> u64 {
+        match self.txs.iter().rev().find(|tx| tx.l1_msg) {
+            // note: last queue index included before this block is total_l1_message_popped_before - 1
+            Some(tx) => tx.nonce - total_l1_message_popped_before + 1,
+            None => 0,
+        }
+    }
+
+    fn encode(&self, total_l1_message_popped_before: u64) -> Result<Vec<u8>, BatchError> {
+        v0::DABlock::from(self.clone()).encode(total_l1_message_popped_before)
+    }
+}
+
+impl From<BatchChunkBlock> for v0::DABlock {
+    fn from(blk: BatchChunkBlock) -> Self {
+        Self {
+            number: blk.number,
+            timestamp: blk.timestamp,
+            base_fee: blk.base_fee,
+            gas_limit: blk.gas_limit,
+            hash: blk.hash,
+            txs: blk.txs.into_iter().map(Into::into).collect(),
         }
-        bytes.extend_from_slice(&base_fee_bytes);
-        bytes.extend_from_slice(&self.gas_limit.to_be_bytes());
-        bytes.extend_from_slice(&(num_transactions as u16).to_be_bytes());
-        bytes.extend_from_slice(&(num_l1_messages as u16).to_be_bytes());
-        Ok(bytes)
     }
 }
 
@@ -139,51 +158,198 @@ pub struct BatchChunkBlockTx {
     pub encode: Vec<u8>,
 }
 
+impl TxTrait for BatchChunkBlockTx {
+    fn new(tx: &Transaction, rlp: Vec<u8>) -> Self {
+        v0::DABlockTx::new(tx, rlp).into()
+    }
+
+    fn from_raw(tx: RawTx) -> Self {
+        v0::DABlockTx::from_raw(tx).into()
+    }
+
+    fn is_l1_msg(&self) -> bool {
+        self.l1_msg
+    }
+
+    fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    fn hash(&self) -> B256 {
+        self.tx_hash
+    }
+
+    fn rlp_bytes(&self) -> &[u8] {
+        &self.encode
+    }
+}
+
+impl From<BatchChunkBlockTx> for v0::DABlockTx {
+    fn from(tx: BatchChunkBlockTx) -> Self {
+        Self {
+            l1_msg: tx.l1_msg,
+            nonce: tx.nonce,
+            tx_hash: tx.tx_hash,
+            rlp: tx.encode,
+        }
+    }
+}
+
+impl From<v0::DABlockTx> for BatchChunkBlockTx {
+    fn from(tx: v0::DABlockTx) -> Self {
+        Self {
+            l1_msg: tx.l1_msg,
+            nonce: tx.nonce,
+            tx_hash: tx.tx_hash,
+            encode: tx.rlp,
+        }
+    }
+}
+
+/// Groups plain blocks into the chunks given by block numbers, see
+/// VersionedBatchBuilder for the Context based equivalent.
 pub struct BatchChunkBuilder {
-    pub numbers: Vec<Vec<u64>>,
-    pub chunks: Vec<BatchChunk>,
-    current_chunk_id: usize,
-    current_block_id: usize,
+    pub version: u8,
+    slots: BlockSlots<BatchChunkBlock>,
 }
 
 impl BatchChunkBuilder {
     pub fn new(numbers: Vec<Vec<u64>>) -> Self {
+        Self::with_version(v0::VERSION, numbers)
+    }
+
+    /// Same as new, the chunks are hashed the way of the codec `version`.
+    pub fn with_version(version: u8, numbers: Vec<Vec<u64>>) -> Self {
         Self {
-            chunks: Vec::with_capacity(numbers.len()),
-            numbers,
-            current_chunk_id: 0,
-            current_block_id: 0,
+            version,
+            slots: BlockSlots::new(&numbers),
         }
     }
 
-    pub fn add_block(&mut self, block: BatchChunkBlock) -> Result<(), String> {
-        for (chunk_id, chunk) in self.numbers.iter().enumerate() {
-            for (block_id, blkno) in chunk.iter().enumerate() {
-                if blkno == &block.number {
-                    let mut expect_chunk_id = self.current_chunk_id;
-                    let mut expect_block_id = self.current_block_id;
-                    if expect_block_id == self.numbers[self.current_chunk_id].len() {
-                        expect_chunk_id += 1;
-                        expect_block_id = 0;
-                    }
-                    if expect_block_id != block_id || expect_chunk_id != chunk_id {
-                        return Err(format!(
-                            "unexpected block, want=[{}.{}], got=[{}.{}]",
-                            expect_block_id, expect_chunk_id, block_id, chunk_id
-                        ));
-                    }
-                    if block_id == 0 {
-                        self.chunks.push(BatchChunk { blocks: Vec::new() });
-                    }
-                    let chunk = self.chunks.get_mut(chunk_id).unwrap();
-                    chunk.blocks.push(block);
-
-                    self.current_chunk_id = chunk_id;
-                    self.current_block_id = block_id + 1;
-                    return Ok(());
-                }
+    pub fn add_block(&mut self, block: BatchChunkBlock) -> Result<(), BatchError> {
+        self.slots.insert(block.number, block)
+    }
+
+    pub fn missing_blocks(&self) -> Vec<u64> {
+        self.slots.missing()
+    }
+
+    pub fn build(self) -> Result<Vec<BatchChunk>, BatchError> {
+        let version = self.version;
+        Ok(self
+            .slots
+            .into_chunks()?
+            .into_iter()
+            .map(|blocks| BatchChunk { version, blocks })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{v3, BatchBuilder, BatchTrait, DABatch};
+
+    fn test_block(number: u64, l1_nonce: Option<u64>) -> BatchChunkBlock {
+        let mut txs = Vec::new();
+        if let Some(nonce) = l1_nonce {
+            let encode = vec![0x7e, 0xc1, nonce as u8];
+            txs.push(BatchChunkBlockTx {
+                l1_msg: true,
+                nonce,
+                tx_hash: base::eth::primitives::keccak256(&encode),
+                encode,
+            });
+        }
+        let encode = vec![0xc2, 0x01, number as u8];
+        txs.push(BatchChunkBlockTx {
+            l1_msg: false,
+            nonce: number,
+            tx_hash: base::eth::primitives::keccak256(&encode),
+            encode,
+        });
+        BatchChunkBlock {
+            number,
+            timestamp: 1000 + number,
+            base_fee: Some(U256::from(7)),
+            gas_limit: 10_000_000,
+            hash: B256::default(),
+            txs,
+        }
+    }
+
+    #[test]
+    fn test_batch_chunk_codec_hash() {
+        let numbers = vec![vec![1, 2], vec![3]];
+        let blocks = vec![
+            test_block(1, Some(5)),
+            test_block(2, None),
+            test_block(3, Some(7)),
+        ];
+
+        for version in [v0::VERSION, v3::VERSION] {
+            let mut legacy = BatchChunkBuilder::with_version(version, numbers.clone());
+            let parent = match version {
+                v0::VERSION => DABatch::V0(Default::default()),
+                _ => DABatch::V3(v3::DABatch {
+                    version: v3::VERSION,
+                    total_l1_message_popped: 5,
+                    ..Default::default()
+                }),
+            };
+            let mut builder =
+                BatchBuilder::with_numbers(version, &parent, numbers.clone()).unwrap();
+            for block in &blocks {
+                legacy.add_block(block.clone()).unwrap();
+                builder.add_block(block.clone()).unwrap();
             }
+            assert!(legacy.add_block(test_block(4, None)).is_err());
+
+            let batch = builder.build(parent.clone()).unwrap();
+            let chunks = legacy.build().unwrap();
+            let legacy_batch = match parent {
+                DABatch::V0(parent) => DABatch::V0(BatchTrait::new(parent, chunks).unwrap()),
+                DABatch::V3(parent) => DABatch::V3(BatchTrait::new(parent, chunks).unwrap()),
+                _ => unreachable!(),
+            };
+            assert_eq!(batch, legacy_batch, "version {}", version);
+        }
+    }
+
+    #[test]
+    fn test_batch_chunk_encode_skipped() {
+        // queue indices 0 and 1 are skipped, 2 is included
+        let chunk = BatchChunk {
+            version: v0::VERSION,
+            blocks: vec![test_block(8, Some(2))],
+        };
+        let context = |num_txs: &str, num_l1_msgs: &str| {
+            format!(
+                "{}{}{}07{}{}{}",
+                "0000000000000008",
+                "00000000000003f0",
+                "00".repeat(31),
+                "0000000000989680",
+                num_txs,
+                num_l1_msgs,
+            )
+        };
+
+        let encoded = chunk.encode(0).unwrap();
+        let expect = format!("01{}00000003c20108", context("0004", "0003"));
+        assert_eq!(hex::encode(&encoded), expect);
+
+        let encoded = chunk.encode(2).unwrap();
+        let expect = format!("01{}00000003c20108", context("0002", "0001"));
+        assert_eq!(hex::encode(&encoded), expect);
+
+        let mut data_bytes = hex::decode(context("0004", "0003")).unwrap()[..58].to_vec();
+        for tx in &chunk.blocks[0].txs {
+            data_bytes.extend_from_slice(tx.tx_hash.as_slice());
         }
-        return Err("unknown block".into());
+        assert_eq!(
+            chunk.hash(v0::VERSION, 0).unwrap(),
+            base::eth::primitives::keccak256(&data_bytes)
+        );
     }
 }
@@ -2,10 +2,303 @@ use base::{eth::primitives::B256, prover::Poe};
 use serde::{Deserialize, Serialize};
 
 use super::{
-    decode_block_numbers, solidity_parse_array_bytes, solidity_parse_bytes, BatchBuilder,
-    BatchContext, BatchError, DABatch,
+    decode_block_numbers, solidity_encode, solidity_parse_array_bytes, solidity_parse_bytes,
+    solidity_parse_usize, solidity_parse_word, solidity_word_u64, split_selector, v0, v1, v2, v3,
+    v4, BatchBuildReport, BatchBuilder, BatchContext, BatchError, BlockHeaderInfo, DABatch, RawTx,
+    SolidityValue,
 };
 
+// commitBatch(uint8,bytes,bytes[],bytes)
+pub const COMMIT_BATCH_SELECTOR: [u8; 4] = [0x13, 0x25, 0xac, 0xa0];
+// commitBatchWithBlobProof(uint8,bytes,bytes[],bytes,bytes)
+pub const COMMIT_BATCH_WITH_BLOB_PROOF_SELECTOR: [u8; 4] = [0x86, 0xb0, 0x53, 0xa9];
+
+// finalizeBatch(bytes,bytes32,bytes32,bytes32)
+pub const FINALIZE_BATCH_SELECTOR: [u8; 4] = [0xa4, 0x78, 0x30, 0xe8];
+// finalizeBatch4844(bytes,bytes32,bytes32,bytes32,bytes)
+pub const FINALIZE_BATCH_4844_SELECTOR: [u8; 4] = [0x68, 0x48, 0x5e, 0x47];
+// finalizeBatchWithProof(bytes,bytes32,bytes32,bytes32,bytes)
+pub const FINALIZE_BATCH_WITH_PROOF_SELECTOR: [u8; 4] = [0x31, 0xfa, 0x74, 0x2d];
+// finalizeBatchWithProof4844(bytes,bytes32,bytes32,bytes32,bytes,bytes)
+pub const FINALIZE_BATCH_WITH_PROOF_4844_SELECTOR: [u8; 4] = [0x00, 0xb0, 0xf4, 0xd7];
+// finalizeBundle(bytes,bytes32,bytes32)
+pub const FINALIZE_BUNDLE_SELECTOR: [u8; 4] = [0xeb, 0x43, 0xa1, 0x33];
+// finalizeBundleWithProof(bytes,bytes32,bytes32,bytes)
+pub const FINALIZE_BUNDLE_WITH_PROOF_SELECTOR: [u8; 4] = [0x4f, 0x09, 0x9e, 0x3d];
+
+/// A decoded ScrollChain commit transaction input.
+#[derive(Debug, Clone)]
+pub enum CommitBatchCall {
+    CommitBatch {
+        version: u8,
+        parent_batch_header: DABatch,
+        chunks: Vec<Vec<u8>>,
+        skipped_l1_message_bitmap: Vec<u8>,
+    },
+    CommitBatchWithBlobProof {
+        version: u8,
+        parent_batch_header: DABatch,
+        chunks: Vec<Vec<u8>>,
+        skipped_l1_message_bitmap: Vec<u8>,
+        blob_data_proof: Vec<u8>,
+    },
+}
+
+impl CommitBatchCall {
+    /// Decodes a raw transaction input, selector included.
+    pub fn from_calldata(input: &[u8]) -> Result<Self, BatchError> {
+        let (selector, data) = split_selector(input)?;
+        // commitBatch takes v0 to v2, the blob data proof is required for v3 and v4
+        let versions = match selector {
+            COMMIT_BATCH_SELECTOR => v0::VERSION..=v2::VERSION,
+            COMMIT_BATCH_WITH_BLOB_PROOF_SELECTOR => v3::VERSION..=v4::VERSION,
+            selector => return Err(BatchError::UnknownSelector(selector)),
+        };
+
+        let version = solidity_parse_usize(0, data)?;
+        let version = u8::try_from(version).map_err(|_| BatchError::InvalidCallVersion(version))?;
+        if !versions.contains(&version) {
+            return Err(BatchError::SelectorVersionMismatch { selector, version });
+        }
+        let parent_batch_header = DABatch::from_bytes(&solidity_parse_bytes(32, data)?)?;
+        let chunks = solidity_parse_array_bytes(64, data)?;
+        let skipped_l1_message_bitmap = solidity_parse_bytes(96, data)?;
+
+        Ok(match selector {
+            COMMIT_BATCH_SELECTOR => Self::CommitBatch {
+                version,
+                parent_batch_header,
+                chunks,
+                skipped_l1_message_bitmap,
+            },
+            _ => Self::CommitBatchWithBlobProof {
+                version,
+                parent_batch_header,
+                chunks,
+                skipped_l1_message_bitmap,
+                blob_data_proof: solidity_parse_bytes(128, data)?,
+            },
+        })
+    }
+
+    /// The call committing `batch` on top of `parent_batch_header`, with the
+    /// chunks encoded by ChunkTrait::calldata. Since v3 the call carries the
+    /// blobDataProof, see BlobPayload::blob_data_proof.
+    pub fn new(
+        batch: &DABatch,
+        parent_batch_header: DABatch,
+        chunks: Vec<Vec<u8>>,
+        blob_data_proof: Option<Vec<u8>>,
+    ) -> Result<Self, BatchError> {
+        let parent_hash = parent_batch_header.hash();
+        if batch.parent_batch_hash() != parent_hash {
+            return Err(BatchError::ParentBatchHashMismatch {
+                batch_index: batch.batch_index(),
+                want: parent_hash,
+                got: batch.parent_batch_hash(),
+            });
+        }
+
+        let version = batch.version();
+        let skipped_l1_message_bitmap = batch
+            .skipped_l1_message_bitmap()
+            .map(|n| n.to_vec())
+            .unwrap_or_default();
+        if version < v3::VERSION {
+            return Ok(Self::CommitBatch {
+                version,
+                parent_batch_header,
+                chunks,
+                skipped_l1_message_bitmap,
+            });
+        }
+        Ok(Self::CommitBatchWithBlobProof {
+            version,
+            parent_batch_header,
+            chunks,
+            skipped_l1_message_bitmap,
+            blob_data_proof: blob_data_proof.ok_or(BatchError::MissingBlobDataProof { version })?,
+        })
+    }
+
+    /// Encodes the transaction input, selector included.
+    pub fn to_calldata(&self) -> Vec<u8> {
+        let parent_batch_header = self.parent_batch_header().encode();
+        let mut args = vec![
+            SolidityValue::Word(solidity_word_u64(self.version() as u64)),
+            SolidityValue::Bytes(&parent_batch_header),
+            SolidityValue::ArrayBytes(self.chunks()),
+            SolidityValue::Bytes(self.skipped_l1_message_bitmap()),
+        ];
+        if let Some(blob_data_proof) = self.blob_data_proof() {
+            args.push(SolidityValue::Bytes(blob_data_proof));
+        }
+        solidity_encode(self.selector(), &args)
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        match self {
+            Self::CommitBatch { .. } => COMMIT_BATCH_SELECTOR,
+            Self::CommitBatchWithBlobProof { .. } => COMMIT_BATCH_WITH_BLOB_PROOF_SELECTOR,
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        match self {
+            Self::CommitBatch { version, .. } => *version,
+            Self::CommitBatchWithBlobProof { version, .. } => *version,
+        }
+    }
+
+    pub fn parent_batch_header(&self) -> &DABatch {
+        match self {
+            Self::CommitBatch {
+                parent_batch_header,
+                ..
+            } => parent_batch_header,
+            Self::CommitBatchWithBlobProof {
+                parent_batch_header,
+                ..
+            } => parent_batch_header,
+        }
+    }
+
+    pub fn chunks(&self) -> &[Vec<u8>] {
+        match self {
+            Self::CommitBatch { chunks, .. } => chunks,
+            Self::CommitBatchWithBlobProof { chunks, .. } => chunks,
+        }
+    }
+
+    pub fn skipped_l1_message_bitmap(&self) -> &[u8] {
+        match self {
+            Self::CommitBatch {
+                skipped_l1_message_bitmap,
+                ..
+            } => skipped_l1_message_bitmap,
+            Self::CommitBatchWithBlobProof {
+                skipped_l1_message_bitmap,
+                ..
+            } => skipped_l1_message_bitmap,
+        }
+    }
+
+    pub fn blob_data_proof(&self) -> Option<&[u8]> {
+        match self {
+            Self::CommitBatch { .. } => None,
+            Self::CommitBatchWithBlobProof {
+                blob_data_proof, ..
+            } => Some(blob_data_proof),
+        }
+    }
+
+    pub fn batch_task(&self) -> Result<BatchTask, BatchError> {
+        Ok(BatchTask {
+            chunks: BatchTask::decode_chunks(self.chunks())?,
+            parent_batch_header: self.parent_batch_header().clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalizeField {
+    PrevStateRoot,
+    NewStateRoot,
+    WithdrawalRoot,
+    BatchHash,
+    /// The poe at this index doesn't start from the new_state_root of the previous one.
+    PoePrevStateRoot(usize),
+    /// No poe for the batch, expected carries the batch hash.
+    MissingPoe,
+    /// More than one poe for a single batch, expected carries the batch hash.
+    TooManyPoes {
+        got: usize,
+    },
+    /// The prev_state_root of a bundle wasn't supplied, so the first poe can't be checked.
+    UnknownPrevStateRoot,
+}
+
+/// expected is committed on L1, actual is produced by the verifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalizeFieldMismatch {
+    pub field: FinalizeField,
+    pub expected: B256,
+    pub actual: B256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalizeMismatch {
+    pub batch_index: u64,
+    pub fields: Vec<FinalizeFieldMismatch>,
+}
+
+impl FinalizeMismatch {
+    fn new(batch_index: u64) -> Self {
+        Self {
+            batch_index,
+            fields: Vec::new(),
+        }
+    }
+
+    fn poe_count(batch: &DABatch, got: usize) -> Self {
+        let field = match got {
+            0 => FinalizeField::MissingPoe,
+            got => FinalizeField::TooManyPoes { got },
+        };
+        let mut mismatch = Self::new(batch.batch_index());
+        mismatch.fields.push(FinalizeFieldMismatch {
+            field,
+            expected: batch.hash(),
+            actual: B256::default(),
+        });
+        mismatch
+    }
+
+    /// The prev_state_root isn't in the calldata of a bundle, it must not pass
+    /// unchecked.
+    fn check_prev_state_root(&mut self, expected: Option<B256>, actual: B256) {
+        match expected {
+            Some(expected) => self.check(FinalizeField::PrevStateRoot, expected, actual),
+            None => self.fields.push(FinalizeFieldMismatch {
+                field: FinalizeField::UnknownPrevStateRoot,
+                expected: B256::default(),
+                actual,
+            }),
+        }
+    }
+
+    fn check(&mut self, field: FinalizeField, expected: B256, actual: B256) {
+        if expected != actual {
+            self.fields.push(FinalizeFieldMismatch {
+                field,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    fn into_result(self) -> Result<(), Self> {
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+        Err(self)
+    }
+}
+
+impl std::fmt::Display for FinalizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "batch #{} mismatch:", self.batch_index)?;
+        for item in &self.fields {
+            write!(
+                f,
+                " {:?}(expected={:?}, actual={:?})",
+                item.field, item.expected, item.actual
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Finalize {
     pub batch: DABatch,
@@ -15,19 +308,20 @@ pub struct Finalize {
 }
 
 impl Finalize {
+    /// Decodes the arguments of a finalize call without checking them against
+    /// the selector, batches before v3 carry the prevStateRoot.
     pub fn from_calldata(data: &[u8]) -> Result<Self, BatchError> {
-        let batch = solidity_parse_bytes(0, data);
+        let batch = solidity_parse_bytes(0, data)?;
         let batch = DABatch::from_bytes(&batch)?;
-        let mut off = 1;
+        let mut off = 32;
         let mut prev_state_root = None;
-        if batch.version() <= 2 {
-            prev_state_root = Some(B256::from_slice(&data[off * 32..][..32]));
-            off += 1;
+        if batch.version() < v3::VERSION {
+            prev_state_root = Some(B256::from_slice(solidity_parse_word(off, data)?));
+            off += 32;
         }
-        let new_state_root = B256::from_slice(&data[off * 32..][..32]);
-        off += 1;
-
-        let new_withdrawal_root = B256::from_slice(&data[off * 32..][..32]);
+        let new_state_root = B256::from_slice(solidity_parse_word(off, data)?);
+        off += 32;
+        let new_withdrawal_root = B256::from_slice(solidity_parse_word(off, data)?);
 
         Ok(Self {
             batch,
@@ -37,22 +331,293 @@ impl Finalize {
         })
     }
 
+    /// Decodes the arguments of a finalizeBatch* call, all of them carry the
+    /// prevStateRoot. v0 batches go with the variants without the blob data
+    /// proof, v1 and v2 with the 4844 ones, and v3 on with the bundles.
+    pub fn from_selector_calldata(selector: [u8; 4], data: &[u8]) -> Result<Self, BatchError> {
+        let versions = match selector {
+            FINALIZE_BATCH_SELECTOR | FINALIZE_BATCH_WITH_PROOF_SELECTOR => {
+                v0::VERSION..=v0::VERSION
+            }
+            FINALIZE_BATCH_4844_SELECTOR | FINALIZE_BATCH_WITH_PROOF_4844_SELECTOR => {
+                v1::VERSION..=v2::VERSION
+            }
+            selector => return Err(BatchError::UnknownSelector(selector)),
+        };
+        let batch = solidity_parse_bytes(0, data)?;
+        let batch = DABatch::from_bytes(&batch)?;
+        let version = batch.version();
+        if version >= v3::VERSION {
+            return Err(BatchError::FinalizeBundleRequired { version });
+        }
+        if !versions.contains(&version) {
+            return Err(BatchError::SelectorVersionMismatch { selector, version });
+        }
+        Self::from_calldata(data)
+    }
+
+    /// The finalize call of a proven batch before v3, checked against its poe.
+    pub fn from_poe(batch: DABatch, poe: &Poe) -> Result<Self, FinalizeMismatch> {
+        let finalize = Self {
+            prev_state_root: Some(poe.prev_state_root),
+            batch,
+            new_state_root: poe.new_state_root,
+            new_withdrawal_root: poe.withdrawal_root,
+        };
+        finalize.check_poe(poe)?;
+        Ok(finalize)
+    }
+
+    /// Encodes the transaction input, selector included. v1 and v2 batches
+    /// need the blobDataProof, the aggr_proof selects the WithProof variant.
+    pub fn to_calldata(
+        &self,
+        blob_data_proof: Option<&[u8]>,
+        aggr_proof: Option<&[u8]>,
+    ) -> Result<Vec<u8>, BatchError> {
+        let version = self.batch.version();
+        if version >= v3::VERSION {
+            return Err(BatchError::FinalizeBundleRequired { version });
+        }
+        let prev_state_root = self
+            .prev_state_root
+            .ok_or(BatchError::MissingPrevStateRoot {
+                batch_index: self.batch.batch_index(),
+            })?;
+        let batch = self.batch.encode();
+        let mut args = vec![
+            SolidityValue::Bytes(&batch),
+            SolidityValue::Word(prev_state_root),
+            SolidityValue::Word(self.new_state_root),
+            SolidityValue::Word(self.new_withdrawal_root),
+        ];
+        if version > v0::VERSION {
+            let blob_data_proof =
+                blob_data_proof.ok_or(BatchError::MissingBlobDataProof { version })?;
+            args.push(SolidityValue::Bytes(blob_data_proof));
+        }
+        let selector = match (version, aggr_proof) {
+            (v0::VERSION, None) => FINALIZE_BATCH_SELECTOR,
+            (v0::VERSION, Some(_)) => FINALIZE_BATCH_WITH_PROOF_SELECTOR,
+            (_, None) => FINALIZE_BATCH_4844_SELECTOR,
+            (_, Some(_)) => FINALIZE_BATCH_WITH_PROOF_4844_SELECTOR,
+        };
+        if let Some(aggr_proof) = aggr_proof {
+            args.push(SolidityValue::Bytes(aggr_proof));
+        }
+        Ok(solidity_encode(selector, &args))
+    }
+
+    pub fn check_poe(&self, poe: &Poe) -> Result<(), FinalizeMismatch> {
+        let mut mismatch = FinalizeMismatch::new(self.batch.batch_index());
+        mismatch.check_prev_state_root(self.prev_state_root, poe.prev_state_root);
+        mismatch.check(
+            FinalizeField::NewStateRoot,
+            self.new_state_root,
+            poe.new_state_root,
+        );
+        mismatch.check(
+            FinalizeField::WithdrawalRoot,
+            self.new_withdrawal_root,
+            poe.withdrawal_root,
+        );
+        mismatch.check(FinalizeField::BatchHash, self.batch.hash(), poe.batch_hash);
+        mismatch.into_result()
+    }
+
     pub fn assert_poe(&self, poe: &Poe) {
-        if let Some(prev_state_root) = self.prev_state_root {
-            assert_eq!(
-                prev_state_root, poe.prev_state_root,
-                "prev_state_root mismatch"
+        if let Err(mismatch) = self.check_poe(poe) {
+            panic!("{}", mismatch);
+        }
+    }
+}
+
+/// Finalizes a bundle of consecutive batches, from Darwin on.
+///
+/// The calldata only carries the header of the last batch in the bundle, the
+/// prev_state_root is read from the contract storage and has to be supplied
+/// with `with_prev_state_root` before checking the poes.
+#[derive(Debug, Clone)]
+pub struct FinalizeBundle {
+    pub batch: DABatch,
+    pub prev_state_root: Option<B256>,
+    pub new_state_root: B256,
+    pub new_withdrawal_root: B256,
+}
+
+impl FinalizeBundle {
+    pub fn from_calldata(data: &[u8]) -> Result<Self, BatchError> {
+        let batch = solidity_parse_bytes(0, data)?;
+        let batch = DABatch::from_bytes(&batch)?;
+        let new_state_root = B256::from_slice(solidity_parse_word(32, data)?);
+        let new_withdrawal_root = B256::from_slice(solidity_parse_word(64, data)?);
+
+        Ok(Self {
+            batch,
+            prev_state_root: None,
+            new_state_root,
+            new_withdrawal_root,
+        })
+    }
+
+    /// The bundle finalizing the poes of consecutive batches, in order, with
+    /// `batch` the header of the last one.
+    pub fn from_poes(batch: DABatch, poes: &[Poe]) -> Result<Self, FinalizeMismatch> {
+        let (first, last) = match (poes.first(), poes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(FinalizeMismatch::poe_count(&batch, 0)),
+        };
+        let bundle = Self {
+            prev_state_root: Some(first.prev_state_root),
+            new_state_root: last.new_state_root,
+            new_withdrawal_root: last.withdrawal_root,
+            batch,
+        };
+        bundle.check_poes(poes)?;
+        Ok(bundle)
+    }
+
+    /// Encodes the transaction input, selector included. The aggr_proof
+    /// selects finalizeBundleWithProof.
+    pub fn to_calldata(&self, aggr_proof: Option<&[u8]>) -> Vec<u8> {
+        let batch = self.batch.encode();
+        let mut args = vec![
+            SolidityValue::Bytes(&batch),
+            SolidityValue::Word(self.new_state_root),
+            SolidityValue::Word(self.new_withdrawal_root),
+        ];
+        let selector = match aggr_proof {
+            Some(aggr_proof) => {
+                args.push(SolidityValue::Bytes(aggr_proof));
+                FINALIZE_BUNDLE_WITH_PROOF_SELECTOR
+            }
+            None => FINALIZE_BUNDLE_SELECTOR,
+        };
+        solidity_encode(selector, &args)
+    }
+
+    pub fn with_prev_state_root(mut self, prev_state_root: B256) -> Self {
+        self.prev_state_root = Some(prev_state_root);
+        self
+    }
+
+    /// Checks the bundle against the poes of every batch in it, in order.
+    pub fn check_poes(&self, poes: &[Poe]) -> Result<(), FinalizeMismatch> {
+        let (first, last) = match (poes.first(), poes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(FinalizeMismatch::poe_count(&self.batch, 0)),
+        };
+        let mut mismatch = FinalizeMismatch::new(self.batch.batch_index());
+        for (idx, pair) in poes.windows(2).enumerate() {
+            mismatch.check(
+                FinalizeField::PoePrevStateRoot(idx + 1),
+                pair[0].new_state_root,
+                pair[1].prev_state_root,
             );
         }
-        assert_eq!(
-            self.new_state_root, poe.new_state_root,
-            "new_state_root mismatch"
+        mismatch.check_prev_state_root(self.prev_state_root, first.prev_state_root);
+        mismatch.check(
+            FinalizeField::NewStateRoot,
+            self.new_state_root,
+            last.new_state_root,
         );
-        assert_eq!(
-            self.new_withdrawal_root, poe.withdrawal_root,
-            "withdrawal_root mismatch"
+        mismatch.check(
+            FinalizeField::WithdrawalRoot,
+            self.new_withdrawal_root,
+            last.withdrawal_root,
         );
-        assert_eq!(self.batch.hash(), poe.batch_hash, "batch_hash mismatch");
+        mismatch.check(FinalizeField::BatchHash, self.batch.hash(), last.batch_hash);
+        mismatch.into_result()
+    }
+
+    pub fn assert_poes(&self, poes: &[Poe]) {
+        if let Err(mismatch) = self.check_poes(poes) {
+            panic!("{}", mismatch);
+        }
+    }
+}
+
+/// A decoded ScrollChain finalize transaction input.
+#[derive(Debug, Clone)]
+pub enum FinalizeCall {
+    Batch(Finalize),
+    Bundle(FinalizeBundle),
+}
+
+impl FinalizeCall {
+    /// Decodes a raw transaction input, selector included.
+    pub fn from_calldata(input: &[u8]) -> Result<Self, BatchError> {
+        let (selector, data) = split_selector(input)?;
+        Ok(match selector {
+            FINALIZE_BATCH_SELECTOR
+            | FINALIZE_BATCH_4844_SELECTOR
+            | FINALIZE_BATCH_WITH_PROOF_SELECTOR
+            | FINALIZE_BATCH_WITH_PROOF_4844_SELECTOR => {
+                Self::Batch(Finalize::from_selector_calldata(selector, data)?)
+            }
+            FINALIZE_BUNDLE_SELECTOR | FINALIZE_BUNDLE_WITH_PROOF_SELECTOR => {
+                Self::Bundle(FinalizeBundle::from_calldata(data)?)
+            }
+            selector => return Err(BatchError::UnknownSelector(selector)),
+        })
+    }
+
+    /// Finalizes the poes of consecutive batches ending with `batch`: a single
+    /// batch before v3, a bundle since.
+    pub fn from_poes(batch: DABatch, poes: &[Poe]) -> Result<Self, FinalizeMismatch> {
+        if batch.version() >= v3::VERSION {
+            return Ok(Self::Bundle(FinalizeBundle::from_poes(batch, poes)?));
+        }
+        match poes {
+            [poe] => Ok(Self::Batch(Finalize::from_poe(batch, poe)?)),
+            _ => Err(FinalizeMismatch::poe_count(&batch, poes.len())),
+        }
+    }
+
+    /// Encodes the transaction input, selector included, see
+    /// Finalize::to_calldata and FinalizeBundle::to_calldata.
+    pub fn to_calldata(
+        &self,
+        blob_data_proof: Option<&[u8]>,
+        aggr_proof: Option<&[u8]>,
+    ) -> Result<Vec<u8>, BatchError> {
+        match self {
+            Self::Batch(finalize) => finalize.to_calldata(blob_data_proof, aggr_proof),
+            Self::Bundle(bundle) => Ok(bundle.to_calldata(aggr_proof)),
+        }
+    }
+
+    /// Supplies the state root the bundle starts from, the one of a single
+    /// batch is part of its calldata already.
+    pub fn with_prev_state_root(self, prev_state_root: B256) -> Self {
+        match self {
+            Self::Batch(finalize) => Self::Batch(finalize),
+            Self::Bundle(bundle) => Self::Bundle(bundle.with_prev_state_root(prev_state_root)),
+        }
+    }
+
+    /// The header of the last batch being finalized.
+    pub fn batch(&self) -> &DABatch {
+        match self {
+            Self::Batch(finalize) => &finalize.batch,
+            Self::Bundle(bundle) => &bundle.batch,
+        }
+    }
+
+    pub fn check_poes(&self, poes: &[Poe]) -> Result<(), FinalizeMismatch> {
+        match self {
+            Self::Batch(finalize) => match poes {
+                [poe] => finalize.check_poe(poe),
+                _ => Err(FinalizeMismatch::poe_count(&finalize.batch, poes.len())),
+            },
+            Self::Bundle(bundle) => bundle.check_poes(poes),
+        }
+    }
+
+    pub fn assert_poes(&self, poes: &[Poe]) {
+        if let Err(mismatch) = self.check_poes(poes) {
+            panic!("{}", mismatch);
+        }
     }
 }
 
@@ -81,22 +646,80 @@ impl BatchTask {
         .build(self.parent_batch_header.clone())
     }
 
+    /// Same as build_batch, the blob is committed with the given trusted setup.
+    pub fn build_batch_with_settings<C: BatchContext>(
+        &self,
+        batch_version: u8,
+        blks: &[C],
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<DABatch, BatchError> {
+        BatchBuilder::new(
+            batch_version,
+            self.parent_batch_header.clone(),
+            self.chunks.clone(),
+            blks,
+        )?
+        .build_with_settings(self.parent_batch_header.clone(), settings)
+    }
+
+    /// Same as build_batch_with_settings, also returns the intermediate values
+    /// of the batch for debugging a batch hash mismatch.
+    pub fn build_batch_with_report<C: BatchContext>(
+        &self,
+        batch_version: u8,
+        blks: &[C],
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(DABatch, BatchBuildReport), BatchError> {
+        BatchBuilder::new(
+            batch_version,
+            self.parent_batch_header.clone(),
+            self.chunks.clone(),
+            blks,
+        )?
+        .build_with_report(self.parent_batch_header.clone(), settings)
+    }
+
+    /// Like build_batch_with_settings, but from header fields and raw
+    /// transactions.
+    pub fn build_batch_raw(
+        &self,
+        batch_version: u8,
+        blks: &[(BlockHeaderInfo, Vec<RawTx>)],
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<DABatch, BatchError> {
+        let mut builder = BatchBuilder::with_numbers(
+            batch_version,
+            &self.parent_batch_header,
+            self.chunks.clone(),
+        )?;
+        for (header, txs) in blks {
+            builder.add_raw(header, txs.clone())?;
+        }
+        builder.build_with_settings(self.parent_batch_header.clone(), settings)
+    }
+
     pub fn from_calldata(data: &[u8]) -> Result<BatchTask, BatchError> {
-        let parent_batch_header_bytes = solidity_parse_bytes(32, data);
-        let chunks_bytes = solidity_parse_array_bytes(64, data);
+        let parent_batch_header_bytes = solidity_parse_bytes(32, data)
+            .map_err(BatchError::ParseBatchTaskFromCalldata())?;
+        let chunks_bytes = solidity_parse_array_bytes(64, data)
+            .map_err(BatchError::ParseBatchTaskFromCalldata())?;
         let parent_batch_header = DABatch::from_bytes(&parent_batch_header_bytes)
             .map_err(BatchError::ParseBatchTaskFromCalldata())?;
+        Ok(BatchTask {
+            chunks: Self::decode_chunks(&chunks_bytes)?,
+            parent_batch_header,
+        })
+    }
+
+    fn decode_chunks(chunks_bytes: &[Vec<u8>]) -> Result<Vec<Vec<u64>>, BatchError> {
         let mut outs = Vec::new();
         for chunk_byte in chunks_bytes {
-            match decode_block_numbers(&chunk_byte) {
+            match decode_block_numbers(chunk_byte) {
                 Some(blks) => outs.push(blks),
-                None => return Err(BatchError::InvalidBlockNumbers(chunk_byte.into())),
+                None => return Err(BatchError::InvalidBlockNumbers(chunk_byte.clone().into())),
             }
         }
-        Ok(BatchTask {
-            chunks: outs,
-            parent_batch_header,
-        })
+        Ok(outs)
     }
 
     pub fn block_numbers(&self) -> Vec<u64> {
@@ -111,3 +734,83 @@ impl BatchTask {
         Some(*self.chunks.last()?.last()?)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_finalize_check_poe() {
+        let finalize = Finalize {
+            batch: DABatch::V0(Default::default()),
+            prev_state_root: Some(B256::repeat_byte(1)),
+            new_state_root: B256::repeat_byte(2),
+            new_withdrawal_root: B256::repeat_byte(3),
+        };
+        let mut poe = Poe::default();
+        poe.prev_state_root = B256::repeat_byte(1);
+        poe.new_state_root = B256::repeat_byte(2);
+        poe.withdrawal_root = B256::repeat_byte(3);
+        poe.batch_hash = finalize.batch.hash();
+        assert_eq!(finalize.check_poe(&poe), Ok(()));
+
+        poe.new_state_root = B256::repeat_byte(4);
+        poe.batch_hash = B256::default();
+        let mismatch = finalize.check_poe(&poe).unwrap_err();
+        assert_eq!(
+            mismatch.fields,
+            vec![
+                FinalizeFieldMismatch {
+                    field: FinalizeField::NewStateRoot,
+                    expected: B256::repeat_byte(2),
+                    actual: B256::repeat_byte(4),
+                },
+                FinalizeFieldMismatch {
+                    field: FinalizeField::BatchHash,
+                    expected: finalize.batch.hash(),
+                    actual: B256::default(),
+                },
+            ]
+        );
+
+        let call = FinalizeCall::Batch(finalize.clone());
+        let mismatch = call
+            .check_poes(&[Poe::default(), Poe::default()])
+            .unwrap_err();
+        assert_eq!(
+            mismatch.fields[0].field,
+            FinalizeField::TooManyPoes { got: 2 }
+        );
+        let mismatch = call.check_poes(&[]).unwrap_err();
+        assert_eq!(mismatch.fields[0].field, FinalizeField::MissingPoe);
+    }
+    #[test]
+    fn test_finalize_bundle_check_poes() {
+        let batch = DABatch::V3(Default::default());
+        let mut poes = vec![Poe::default(), Poe::default()];
+        poes[0].prev_state_root = B256::repeat_byte(1);
+        poes[0].new_state_root = B256::repeat_byte(2);
+        poes[1].prev_state_root = B256::repeat_byte(2);
+        poes[1].new_state_root = B256::repeat_byte(3);
+        poes[1].batch_hash = batch.hash();
+        let bundle = FinalizeBundle::from_poes(batch.clone(), &poes).unwrap();
+        assert_eq!(bundle.prev_state_root, Some(B256::repeat_byte(1)));
+
+        // the calldata doesn't carry the prev_state_root, it must not pass unchecked
+        let bundle = FinalizeBundle {
+            prev_state_root: None,
+            ..bundle
+        };
+        let mismatch = bundle.check_poes(&poes).unwrap_err();
+        assert_eq!(
+            mismatch.fields,
+            vec![FinalizeFieldMismatch {
+                field: FinalizeField::UnknownPrevStateRoot,
+                expected: B256::default(),
+                actual: B256::repeat_byte(1),
+            }]
+        );
+        let call = FinalizeCall::Bundle(bundle).with_prev_state_root(B256::repeat_byte(1));
+        assert_eq!(call.check_poes(&poes), Ok(()));
+    }
+}
@@ -0,0 +1,262 @@
+use std::{collections::HashMap, path::Path};
+
+use base::eth::primitives::{Bytes, B256};
+use serde::{Deserialize, Deserializer};
+
+use crate::{calc_blob_hash, BatchError, BlobPayload, DABatch, DecodedBlobPayload};
+
+/// A blob sidecar as served by the beacon API (`/eth/v1/beacon/blob_sidecars/{slot}`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlobSidecar {
+    #[serde(deserialize_with = "deserialize_index")]
+    pub index: u64,
+    pub blob: Bytes,
+    pub kzg_commitment: Bytes,
+    pub kzg_proof: Bytes,
+}
+
+impl BlobSidecar {
+    pub fn blob(&self) -> Result<c_kzg::Blob, BatchError> {
+        Ok(c_kzg::Blob::from_bytes(&self.blob)?)
+    }
+
+    pub fn commitment(&self) -> Result<c_kzg::Bytes48, BatchError> {
+        Ok(c_kzg::Bytes48::from_bytes(&self.kzg_commitment)?)
+    }
+
+    pub fn proof(&self) -> Result<c_kzg::Bytes48, BatchError> {
+        Ok(c_kzg::Bytes48::from_bytes(&self.kzg_proof)?)
+    }
+
+    pub fn versioned_hash(&self) -> Result<B256, BatchError> {
+        Ok(calc_blob_hash(1, &self.commitment()?))
+    }
+
+    /// Decodes the chunk data of the batch carried by this blob.
+    pub fn decode(&self, batch: &DABatch) -> Result<DecodedBlobPayload, BatchError> {
+        self.check_versioned_hash(batch)?;
+        BlobPayload::decode(batch.version(), &self.blob()?)
+    }
+
+    /// Checks the blob against the sidecar proof and the versioned hash in the
+    /// batch header, see verify_blob_data_proof for the [z, y] of v3 and later.
+    pub fn verify(&self, batch: &DABatch, settings: &c_kzg::KzgSettings) -> Result<(), BatchError> {
+        self.check_versioned_hash(batch)?;
+        let valid = c_kzg::KzgProof::verify_blob_kzg_proof(
+            &self.blob()?,
+            &self.commitment()?,
+            &self.proof()?,
+            settings,
+        )?;
+        if !valid {
+            return Err(BatchError::InvalidBlobKzgProof);
+        }
+        Ok(())
+    }
+
+    /// Checks the [z, y] in a v3+ header against the blob, with the point
+    /// proof of the blobDataProof in the commit call (z || y || commitment ||
+    /// proof), see DABatch::verify_blob.
+    pub fn verify_blob_data_proof(
+        &self,
+        batch: &DABatch,
+        blob_data_proof: &[u8],
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(), BatchError> {
+        let proof = blob_data_proof
+            .get(112..160)
+            .ok_or(BatchError::AbiOutOfRange {
+                offset: 112,
+                size: 48,
+                len: blob_data_proof.len(),
+            })?;
+        let proof = c_kzg::Bytes48::from_bytes(proof)?;
+        batch.verify_blob(&self.blob()?, &self.commitment()?, &proof, settings)
+    }
+
+    fn check_versioned_hash(&self, batch: &DABatch) -> Result<(), BatchError> {
+        let want = batch
+            .blob_versioned_hash()
+            .ok_or(BatchError::BlobNotSupported {
+                version: batch.version(),
+            })?;
+        let got = self.versioned_hash()?;
+        if want != got {
+            return Err(BatchError::BlobVersionedHashMismatch { want, got });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BlobSidecarsJson {
+    Response { data: Vec<BlobSidecar> },
+    List(Vec<BlobSidecar>),
+}
+
+/// Blob sidecars archived from the beacon API, looked up by versioned hash.
+#[derive(Debug, Clone, Default)]
+pub struct BlobSidecars {
+    sidecars: Vec<BlobSidecar>,
+    // versioned hash => index in sidecars
+    index: HashMap<B256, usize>,
+}
+
+impl BlobSidecars {
+    /// Accepts either the full beacon API response or the bare `data` list.
+    pub fn from_json(data: &[u8]) -> Result<Self, BatchError> {
+        let sidecars = match serde_json::from_slice(data)? {
+            BlobSidecarsJson::Response { data } => data,
+            BlobSidecarsJson::List(data) => data,
+        };
+        let mut out = Self::default();
+        for sidecar in sidecars {
+            out.push(sidecar)?;
+        }
+        Ok(out)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, BatchError> {
+        Self::from_json(&std::fs::read(path)?)
+    }
+
+    /// Loads every `*.json` file in the directory.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, BatchError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut out = Self::default();
+        for path in paths {
+            out.extend(Self::from_file(path)?);
+        }
+        Ok(out)
+    }
+
+    pub fn sidecars(&self) -> &[BlobSidecar] {
+        &self.sidecars
+    }
+
+    /// The first sidecar of a versioned hash is kept on lookup.
+    pub fn push(&mut self, sidecar: BlobSidecar) -> Result<(), BatchError> {
+        let versioned_hash = sidecar.versioned_hash()?;
+        self.index
+            .entry(versioned_hash)
+            .or_insert(self.sidecars.len());
+        self.sidecars.push(sidecar);
+        Ok(())
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        let offset = self.sidecars.len();
+        for (versioned_hash, idx) in other.index {
+            self.index.entry(versioned_hash).or_insert(offset + idx);
+        }
+        self.sidecars.extend(other.sidecars);
+    }
+
+    pub fn find(&self, versioned_hash: B256) -> Option<&BlobSidecar> {
+        Some(&self.sidecars[*self.index.get(&versioned_hash)?])
+    }
+
+    pub fn for_batch(&self, batch: &DABatch) -> Result<&BlobSidecar, BatchError> {
+        let versioned_hash = batch
+            .blob_versioned_hash()
+            .ok_or(BatchError::BlobNotSupported {
+                version: batch.version(),
+            })?;
+        self.find(versioned_hash)
+            .ok_or(BatchError::BlobSidecarNotFound(versioned_hash))
+    }
+}
+
+// the beacon API encodes the index as a decimal string
+fn deserialize_index<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Index {
+        Num(u64),
+        Str(String),
+    }
+    match Index::deserialize(deserializer)? {
+        Index::Num(n) => Ok(n),
+        Index::Str(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{v3, BlobPayloadCompress, BUILDIN_TRUSTED_SETTING};
+
+    #[test]
+    fn test_blob_sidecar_verify() {
+        let rlp = vec![0xc3, 0x01, 0x02, 0x03];
+        let chunks = vec![v3::DAChunk {
+            blocks: vec![v3::DABlock {
+                number: 1,
+                txs: vec![v3::DABlockTx {
+                    l1_msg: false,
+                    nonce: 0,
+                    tx_hash: base::eth::primitives::keccak256(&rlp),
+                    rlp: rlp.clone(),
+                }],
+                ..Default::default()
+            }],
+        }];
+        let payload =
+            BlobPayload::build(&chunks, v3::MAX_NUM_CHUNKS, BlobPayloadCompress::Zstd).unwrap();
+        let commitment = payload.commitment.to_bytes();
+        let proof = c_kzg::KzgProof::compute_blob_kzg_proof(
+            &payload.blob,
+            &commitment,
+            &BUILDIN_TRUSTED_SETTING,
+        )
+        .unwrap();
+
+        let json = format!(
+            r#"{{"data":[{{"index":"0","blob":"0x{}","kzg_commitment":"0x{}","kzg_proof":"0x{}"}}]}}"#,
+            hex::encode(payload.blob.as_slice()),
+            hex::encode(commitment.as_slice()),
+            hex::encode(proof.to_bytes().as_slice()),
+        );
+        let sidecars = BlobSidecars::from_json(json.as_bytes()).unwrap();
+
+        let mut batch = DABatch::V3(v3::DABatch {
+            version: v3::VERSION,
+            blob_versioned_hash: payload.blob_versioned_hash,
+            blob_data_proof: payload.proof,
+            ..Default::default()
+        });
+        let sidecar = sidecars.for_batch(&batch).unwrap();
+        assert_eq!(sidecar.index, 0);
+        sidecar.verify(&batch, &BUILDIN_TRUSTED_SETTING).unwrap();
+        assert_eq!(sidecar.decode(&batch).unwrap().chunks, vec![vec![rlp]]);
+        let blob_data_proof = payload.blob_data_proof();
+        sidecar
+            .verify_blob_data_proof(&batch, &blob_data_proof, &BUILDIN_TRUSTED_SETTING)
+            .unwrap();
+        assert!(matches!(
+            sidecar.verify_blob_data_proof(
+                &batch,
+                &blob_data_proof[..112],
+                &BUILDIN_TRUSTED_SETTING
+            ),
+            Err(BatchError::AbiOutOfRange { offset: 112, .. })
+        ));
+
+        if let DABatch::V3(b) = &mut batch {
+            b.blob_data_proof[1] = B256::default();
+        }
+        sidecar.verify(&batch, &BUILDIN_TRUSTED_SETTING).unwrap();
+        assert!(sidecar
+            .verify_blob_data_proof(&batch, &blob_data_proof, &BUILDIN_TRUSTED_SETTING)
+            .is_err());
+    }
+}
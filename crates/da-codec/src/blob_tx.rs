@@ -0,0 +1,341 @@
+use alloy::consensus::{
+    BlobTransactionSidecar, SignableTransaction, Signed, TxEip4844, TxEip4844Variant,
+    TxEip4844WithSidecar, TxEnvelope,
+};
+use alloy::eips::eip2718::Encodable2718;
+use alloy::eips::eip4844::{Blob, Bytes48};
+use alloy::primitives::{Address, Parity, Signature, B256, U256};
+
+use crate::{BatchError, BlobPayload, CommitBatchCall};
+
+/// Packages batch blobs into an EIP-4844 transaction, in the network form
+/// that carries the sidecar.
+#[derive(Debug, Clone)]
+pub struct BlobTxBuilder {
+    tx: TxEip4844,
+    sidecar: BlobTransactionSidecar,
+}
+
+/// A signed blob transaction, ready for eth_sendRawTransaction.
+#[derive(Debug, Clone)]
+pub struct SignedBlobTx {
+    pub hash: B256,
+    /// type byte || rlp([tx, blobs, commitments, proofs])
+    pub raw: Vec<u8>,
+    pub envelope: TxEnvelope,
+}
+
+impl BlobTxBuilder {
+    pub fn new(chain_id: u64, to: Address, input: Vec<u8>) -> Self {
+        Self {
+            tx: TxEip4844 {
+                chain_id,
+                to,
+                input: input.into(),
+                ..Default::default()
+            },
+            sidecar: BlobTransactionSidecar::default(),
+        }
+    }
+
+    /// The commitBatch* transaction to ScrollChain with the blob of the batch.
+    pub fn commit_batch(
+        chain_id: u64,
+        scroll_chain: Address,
+        call: &CommitBatchCall,
+        payload: &BlobPayload,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<Self, BatchError> {
+        Self::new(chain_id, scroll_chain, call.to_calldata()).add_blob(payload, settings)
+    }
+
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.tx.nonce = nonce;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: u128) -> Self {
+        self.tx.gas_limit = gas_limit;
+        self
+    }
+
+    pub fn fees(
+        mut self,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+        max_fee_per_blob_gas: u128,
+    ) -> Self {
+        self.tx.max_fee_per_gas = max_fee_per_gas;
+        self.tx.max_priority_fee_per_gas = max_priority_fee_per_gas;
+        self.tx.max_fee_per_blob_gas = max_fee_per_blob_gas;
+        self
+    }
+
+    /// Appends the blob of a batch. The sidecar carries the proof of the
+    /// whole blob, not the one at the challenge point in blob_data_proof.
+    pub fn add_blob(
+        mut self,
+        payload: &BlobPayload,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<Self, BatchError> {
+        let commitment = payload.commitment.to_bytes();
+        let proof = c_kzg::KzgProof::compute_blob_kzg_proof(&payload.blob, &commitment, settings)?;
+        self.sidecar.blobs.push(Blob::from_slice(&payload.blob[..]));
+        self.sidecar.commitments.push(Bytes48::from(*commitment));
+        self.sidecar.proofs.push(Bytes48::from(*proof.to_bytes()));
+        self.tx
+            .blob_versioned_hashes
+            .push(payload.blob_versioned_hash);
+        Ok(self)
+    }
+
+    pub fn build(self) -> Result<TxEip4844Variant, BatchError> {
+        if self.sidecar.blobs.is_empty() {
+            return Err(BatchError::EmptyBlobTx);
+        }
+        Ok(TxEip4844Variant::TxEip4844WithSidecar(
+            TxEip4844WithSidecar::from_tx_and_sidecar(self.tx, self.sidecar),
+        ))
+    }
+
+    /// Signs the transaction hash, `sign` returns r || s || v with v being
+    /// either 27 or 28 as produced by tee::Keypair::sign_digest_ecdsa, or the
+    /// y parity 0 or 1.
+    pub fn sign_with<F>(self, sign: F) -> Result<SignedBlobTx, BatchError>
+    where
+        F: FnOnce(B256) -> [u8; 65],
+    {
+        let tx = self.build()?;
+        let sig = sign(tx.signature_hash());
+        let y_parity = match sig[64] {
+            0 | 27 => false,
+            1 | 28 => true,
+            v => return Err(BatchError::InvalidSignatureV(v)),
+        };
+        let signature = Signature::new(
+            U256::from_be_slice(&sig[..32]),
+            U256::from_be_slice(&sig[32..64]),
+            Parity::Parity(y_parity),
+        );
+        Ok(SignedBlobTx::new(tx.into_signed(signature)))
+    }
+}
+
+impl SignedBlobTx {
+    pub fn new(signed: Signed<TxEip4844Variant>) -> Self {
+        let hash = *signed.hash();
+        let envelope = TxEnvelope::Eip4844(signed);
+        Self {
+            hash,
+            raw: envelope.encoded_2718(),
+            envelope,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::da_batch::blob_codec_params;
+    use crate::{
+        v3, BatchBuilderV3, BatchTrait, BlockHeaderInfo, ChunkTrait, DABatch, RawTx,
+        BUILDIN_TRUSTED_SETTING,
+    };
+    use alloy::eips::eip2718::Decodable2718;
+    use tee::Keypair;
+
+    fn test_chunks() -> Vec<v3::DAChunk> {
+        let mut builder = BatchBuilderV3::new(vec![vec![1]]);
+        let header = BlockHeaderInfo {
+            number: 1,
+            ..Default::default()
+        };
+        builder
+            .add_raw(&header, vec![RawTx::l2(vec![0xc2, 0x01, 0x01])])
+            .unwrap();
+        builder.chunks().unwrap()
+    }
+
+    fn test_payload() -> BlobPayload {
+        let (max_chunks, compress) = blob_codec_params(v3::VERSION).unwrap();
+        BlobPayload::build(&test_chunks(), max_chunks, compress).unwrap()
+    }
+
+    // the commitBatchWithBlobProof call of a v3 batch on top of an empty parent
+    fn test_commit_batch(payload: &BlobPayload) -> CommitBatchCall {
+        let parent = v3::DABatch {
+            version: v3::VERSION,
+            ..Default::default()
+        };
+        let chunks = test_chunks();
+        let calldata = chunks
+            .iter()
+            .map(|chunk| chunk.calldata(v3::VERSION, 0).unwrap())
+            .collect();
+        let batch = v3::DABatch::new(parent.clone(), chunks).unwrap();
+        assert_eq!(batch.blob_versioned_hash, payload.blob_versioned_hash);
+        CommitBatchCall::new(
+            &DABatch::V3(batch),
+            DABatch::V3(parent),
+            calldata,
+            Some(payload.blob_data_proof()),
+        )
+        .unwrap()
+    }
+
+    fn test_builder(chain_id: u64, to: Address, payload: &BlobPayload) -> BlobTxBuilder {
+        BlobTxBuilder::new(chain_id, to, vec![])
+            .add_blob(payload, &BUILDIN_TRUSTED_SETTING)
+            .unwrap()
+            .gas_limit(100_000)
+            .fees(10_000_000_000, 1_000_000_000, 1_000_000_000)
+    }
+
+    fn sign(kp: &Keypair, builder: BlobTxBuilder) -> SignedBlobTx {
+        builder
+            .sign_with(|hash| Keypair::sign_digest_ecdsa(&kp.secret_key(), hash.0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_blob_tx_sign() {
+        let payload = test_payload();
+        let kp = Keypair::new();
+        let signed = sign(&kp, test_builder(534352, Address::repeat_byte(1), &payload));
+
+        let decoded = TxEnvelope::decode_2718(&mut signed.raw.as_slice()).unwrap();
+        assert_eq!(decoded, signed.envelope);
+        let tx = match decoded {
+            TxEnvelope::Eip4844(tx) => tx,
+            tx => panic!("unexpected tx: {:?}", tx),
+        };
+        assert_eq!(*tx.hash(), signed.hash);
+        let signer = tx
+            .signature()
+            .recover_address_from_prehash(&tx.signature_hash())
+            .unwrap();
+        assert_eq!(signer, kp.address());
+
+        let sidecar = match tx.tx() {
+            TxEip4844Variant::TxEip4844WithSidecar(tx) => {
+                assert_eq!(
+                    tx.tx.blob_versioned_hashes,
+                    vec![payload.blob_versioned_hash]
+                );
+                &tx.sidecar
+            }
+            tx => panic!("missing sidecar: {:?}", tx),
+        };
+        let valid = c_kzg::KzgProof::verify_blob_kzg_proof(
+            &c_kzg::Blob::from_bytes(sidecar.blobs[0].as_slice()).unwrap(),
+            &c_kzg::Bytes48::from_bytes(sidecar.commitments[0].as_slice()).unwrap(),
+            &c_kzg::Bytes48::from_bytes(sidecar.proofs[0].as_slice()).unwrap(),
+            &BUILDIN_TRUSTED_SETTING,
+        )
+        .unwrap();
+        assert!(valid);
+
+        assert!(BlobTxBuilder::new(1, Address::ZERO, vec![])
+            .build()
+            .is_err());
+
+        let call = test_commit_batch(&payload);
+        let tx = BlobTxBuilder::commit_batch(
+            1,
+            Address::ZERO,
+            &call,
+            &payload,
+            &BUILDIN_TRUSTED_SETTING,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        assert_eq!(tx.tx().input.to_vec(), call.to_calldata());
+    }
+
+    #[test]
+    fn test_blob_tx_sign_parity() {
+        let payload = test_payload();
+        let kp = Keypair::new();
+        let builder = test_builder(534352, Address::repeat_byte(1), &payload);
+        let hash = builder.clone().build().unwrap().signature_hash();
+        let sig = Keypair::sign_digest_ecdsa(&kp.secret_key(), hash.0);
+        let signed = builder.clone().sign_with(|_| sig).unwrap();
+
+        // the y parity is accepted the same as v
+        let mut parity_sig = sig;
+        parity_sig[64] -= 27;
+        let parity_signed = builder.clone().sign_with(|_| parity_sig).unwrap();
+        assert_eq!(parity_signed.hash, signed.hash);
+        assert_eq!(parity_signed.raw, signed.raw);
+
+        for v in [2, 26, 29, 37] {
+            let mut invalid_sig = sig;
+            invalid_sig[64] = v;
+            assert!(matches!(
+                builder.clone().sign_with(|_| invalid_sig),
+                Err(BatchError::InvalidSignatureV(n)) if n == v
+            ));
+        }
+    }
+
+    // Sends the commitBatchWithBlobProof transaction of a v3 batch to a local
+    // devnode running Cancun from its first prefunded account, e.g.
+    // `anvil --hardfork cancun` and DEVNODE_URL=http://localhost:8545. There
+    // is no ScrollChain on the devnode, the call goes to an account without
+    // code. DEVNODE_URL defaults to http://localhost:8545.
+    #[tokio::test]
+    #[ignore]
+    async fn test_blob_tx_devnode() {
+        use alloy::primitives::{Bytes, U64};
+        use alloy::rpc::types::TransactionReceipt;
+        use base::eth::Eth;
+
+        let url = std::env::var("DEVNODE_URL").unwrap_or("http://localhost:8545".into());
+        let eth = Eth::dial(&url, None).unwrap();
+        let secret =
+            hex::decode("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80")
+                .unwrap();
+        let kp = Keypair::from_secret_key(tee::SecretKey::from_slice(&secret).unwrap());
+
+        let client = eth.client();
+        let chain_id: U64 = client.request("eth_chainId", ()).await.unwrap();
+        let nonce: U64 = client
+            .request("eth_getTransactionCount", (kp.address(), "pending"))
+            .await
+            .unwrap();
+
+        let payload = test_payload();
+        let call = test_commit_batch(&payload);
+        let builder = BlobTxBuilder::commit_batch(
+            chain_id.to(),
+            Address::repeat_byte(1),
+            &call,
+            &payload,
+            &BUILDIN_TRUSTED_SETTING,
+        )
+        .unwrap()
+        .nonce(nonce.to())
+        .gas_limit(1_000_000)
+        .fees(10_000_000_000, 1_000_000_000, 1_000_000_000);
+        let signed = sign(&kp, builder);
+        let hash: B256 = client
+            .request("eth_sendRawTransaction", (Bytes::from(signed.raw.clone()),))
+            .await
+            .unwrap();
+        assert_eq!(hash, signed.hash);
+
+        let receipt = loop {
+            let receipt: Option<TransactionReceipt> = client
+                .request("eth_getTransactionReceipt", (hash,))
+                .await
+                .unwrap();
+            match receipt {
+                Some(receipt) => break receipt,
+                None => tokio::time::sleep(std::time::Duration::from_millis(200)).await,
+            }
+        };
+        assert!(receipt.status());
+        assert_eq!(receipt.blob_gas_used, Some(131072));
+    }
+}
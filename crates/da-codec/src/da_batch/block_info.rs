@@ -0,0 +1,92 @@
+use super::prelude::*;
+
+/// The header fields a block commits to in DA, enough to build a batch
+/// without a scroll_executor::Context.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct BlockHeaderInfo {
+    pub number: u64,
+    pub timestamp: u64,
+    pub base_fee: Option<U256>,
+    pub gas_limit: u64,
+}
+
+/// A transaction as committed in DA, no decoding or signature recovery needed.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RawTx {
+    /// The canonical encoding, including the type byte for typed transactions.
+    pub rlp: Vec<u8>,
+    pub hash: B256,
+    pub l1_msg: bool,
+    /// The L1 message queue index, ignored for L2 transactions.
+    pub queue_index: u64,
+}
+
+impl RawTx {
+    pub fn l2(rlp: Vec<u8>) -> Self {
+        Self {
+            hash: keccak256(&rlp),
+            rlp,
+            l1_msg: false,
+            queue_index: 0,
+        }
+    }
+
+    pub fn l1_message(queue_index: u64, rlp: Vec<u8>) -> Self {
+        Self {
+            hash: keccak256(&rlp),
+            rlp,
+            l1_msg: true,
+            queue_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{v0, v3, BatchTask, DABatch};
+
+    #[test]
+    fn test_build_batch_raw() {
+        let parent = DABatch::V3(v3::DABatch {
+            version: v3::VERSION,
+            total_l1_message_popped: 4,
+            ..Default::default()
+        });
+        let task = BatchTask {
+            chunks: vec![vec![10, 11]],
+            parent_batch_header: parent.clone(),
+        };
+        let blocks: Vec<_> = [10_u64, 11]
+            .into_iter()
+            .map(|number| {
+                let header = BlockHeaderInfo {
+                    number,
+                    timestamp: 100 + number,
+                    base_fee: Some(U256::from(1)),
+                    gas_limit: 10_000_000,
+                };
+                let mut txs = vec![RawTx::l2(vec![0xc2, 0x01, number as u8])];
+                if number == 10 {
+                    txs.insert(0, RawTx::l1_message(4, vec![0x7e, 0xc1, 0x04]));
+                }
+                (header, txs)
+            })
+            .collect();
+        let batch = task
+            .build_batch_raw(v3::VERSION, &blocks, &crate::BUILDIN_TRUSTED_SETTING)
+            .unwrap();
+        assert_eq!(batch.l1_message_popped(), 1);
+        assert_eq!(batch.last_block_timestamp(), Some(111));
+
+        let mut builder =
+            crate::BatchBuilder::with_numbers(v3::VERSION, &parent, task.chunks.clone()).unwrap();
+        for (header, txs) in &blocks {
+            let txs = txs.iter().cloned().map(v0::DABlockTx::from_raw).collect();
+            builder
+                .add_block(v0::DABlock::from_header(header, txs))
+                .unwrap();
+        }
+        assert_eq!(builder.build(parent).unwrap(), batch);
+    }
+}
@@ -1,11 +1,18 @@
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
-use scroll_executor::{Context, Transaction};
+use scroll_executor::{revm::primitives::keccak256, Context, Transaction, B256};
 
 use super::{
-    v0::CodecV0, v1::CodecV1, v2::CodecV2, v3::CodecV3, v4::CodecV4, BatchError, BatchTrait,
-    BatchVersionedType, BlockTrait, ChunkTrait, DABatch, TxTrait,
+    v0::{self, CodecV0},
+    v1::CodecV1,
+    v2::CodecV2,
+    v3::CodecV3,
+    v4::CodecV4,
+    BatchError, BatchTrait, BatchVersionedType, BlobPayloadReport, BlockHeaderInfo, BlockTrait,
+    ChunkTrait, DABatch, RawTx, TxTrait,
 };
+use crate::BUILDIN_TRUSTED_SETTING;
 
 pub type BatchBuilderV0 = VersionedBatchBuilder<CodecV0>;
 pub type BatchBuilderV1 = VersionedBatchBuilder<CodecV1>;
@@ -13,6 +20,31 @@ pub type BatchBuilderV2 = VersionedBatchBuilder<CodecV2>;
 pub type BatchBuilderV3 = VersionedBatchBuilder<CodecV3>;
 pub type BatchBuilderV4 = VersionedBatchBuilder<CodecV4>;
 
+/// The version BatchBuilder builds for a (requested version, parent version)
+/// pair. A request one version behind the parent builds the parent's version,
+/// the hardfork config may lag the fork.
+pub const BATCH_BUILDER_VERSIONS: [((u8, u8), u8); 12] = [
+    ((0, 0), 0),
+    ((1, 0), 1),
+    ((1, 1), 1),
+    ((1, 2), 2),
+    ((2, 1), 2),
+    ((2, 2), 2),
+    ((2, 3), 3),
+    ((3, 2), 3),
+    ((3, 3), 3),
+    ((3, 4), 4),
+    ((4, 3), 4),
+    ((4, 4), 4),
+];
+
+pub fn batch_builder_version(batch_version: u8, parent_version: u8) -> Option<u8> {
+    BATCH_BUILDER_VERSIONS
+        .iter()
+        .find(|(pair, _)| *pair == (batch_version, parent_version))
+        .map(|(_, version)| *version)
+}
+
 pub enum BatchBuilder {
     V0(BatchBuilderV0),
     V1(BatchBuilderV1),
@@ -28,24 +60,7 @@ impl BatchBuilder {
         chunks: Vec<Vec<u64>>,
         blocks: &[C],
     ) -> Result<Self, BatchError> {
-        let mut builder = match (batch_version, parent.version()) {
-            (0, 0) => Self::V0(BatchBuilderV0::new(chunks)),
-            (1, 0) => Self::V1(BatchBuilderV1::new(chunks)),
-            (1, 1) => Self::V1(BatchBuilderV1::new(chunks)),
-            (1, 2) => Self::V2(BatchBuilderV2::new(chunks)),
-            (2, 2) => Self::V2(BatchBuilderV2::new(chunks)),
-            (2, 3) => Self::V3(BatchBuilderV3::new(chunks)),
-            (3, 3) => Self::V3(BatchBuilderV3::new(chunks)),
-            (3, 4) => Self::V4(BatchBuilderV4::new(chunks)),
-            (4, 4) => Self::V4(BatchBuilderV4::new(chunks)),
-
-            (batch_version, pv) => {
-                return Err(BatchError::MismatchBatchVersionAndBlock {
-                    block_batch_version: batch_version,
-                    parent_batch_version: pv,
-                });
-            }
-        };
+        let mut builder = Self::with_numbers(batch_version, &parent, chunks)?;
         for c in blocks {
             match &mut builder {
                 Self::V0(b) => b.add(c)?,
@@ -59,6 +74,64 @@ impl BatchBuilder {
         Ok(builder)
     }
 
+    /// An empty builder, blocks are added with add_block. The built version
+    /// follows BATCH_BUILDER_VERSIONS.
+    pub fn with_numbers(
+        batch_version: u8,
+        parent: &DABatch,
+        chunks: Vec<Vec<u64>>,
+    ) -> Result<Self, BatchError> {
+        let mismatch = BatchError::MismatchBatchVersionAndBlock {
+            block_batch_version: batch_version,
+            parent_batch_version: parent.version(),
+        };
+        let version = match batch_builder_version(batch_version, parent.version()) {
+            Some(version) => version,
+            None => return Err(mismatch),
+        };
+        Ok(match version {
+            0 => Self::V0(BatchBuilderV0::new(chunks)),
+            1 => Self::V1(BatchBuilderV1::new(chunks)),
+            2 => Self::V2(BatchBuilderV2::new(chunks)),
+            3 => Self::V3(BatchBuilderV3::new(chunks)),
+            4 => Self::V4(BatchBuilderV4::new(chunks)),
+            _ => return Err(mismatch),
+        })
+    }
+
+    /// Every codec shares the v0 block, so plain block data such as
+    /// BatchChunkBlock can be added through `into()`.
+    pub fn add_block<B: Into<v0::DABlock>>(&mut self, block: B) -> Result<(), BatchError> {
+        let block = block.into();
+        match self {
+            Self::V0(b) => b.add_block(block),
+            Self::V1(b) => b.add_block(block),
+            Self::V2(b) => b.add_block(block),
+            Self::V3(b) => b.add_block(block),
+            Self::V4(b) => b.add_block(block),
+        }
+    }
+
+    pub fn add_raw(&mut self, header: &BlockHeaderInfo, txs: Vec<RawTx>) -> Result<(), BatchError> {
+        match self {
+            Self::V0(b) => b.add_raw(header, txs),
+            Self::V1(b) => b.add_raw(header, txs),
+            Self::V2(b) => b.add_raw(header, txs),
+            Self::V3(b) => b.add_raw(header, txs),
+            Self::V4(b) => b.add_raw(header, txs),
+        }
+    }
+
+    pub fn missing_blocks(&self) -> Vec<u64> {
+        match self {
+            Self::V0(b) => b.missing_blocks(),
+            Self::V1(b) => b.missing_blocks(),
+            Self::V2(b) => b.missing_blocks(),
+            Self::V3(b) => b.missing_blocks(),
+            Self::V4(b) => b.missing_blocks(),
+        }
+    }
+
     pub fn version(&self) -> u8 {
         match self {
             Self::V0(_) => 0,
@@ -70,16 +143,70 @@ impl BatchBuilder {
     }
 
     pub fn build(self, parent: DABatch) -> Result<DABatch, BatchError> {
+        self.build_with_settings(parent, &BUILDIN_TRUSTED_SETTING)
+    }
+
+    /// Same as build, the blob is committed with the given trusted setup.
+    pub fn build_with_settings(
+        self,
+        parent: DABatch,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<DABatch, BatchError> {
+        Ok(self.build_inner(parent, settings)?.0)
+    }
+
+    /// Same as build_with_settings, also returns the intermediate values of
+    /// the batch.
+    pub fn build_with_report(
+        self,
+        parent: DABatch,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(DABatch, BatchBuildReport), BatchError> {
+        self.build_inner(parent, settings)
+    }
+
+    fn build_inner(
+        self,
+        parent: DABatch,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(DABatch, BatchBuildReport), BatchError> {
         Ok(match (self, parent) {
-            (Self::V0(b), DABatch::V0(parent)) => DABatch::V0(b.build(parent)?),
-            (Self::V1(b), DABatch::V0(parent)) => DABatch::V1(b.build(parent)?),
-            (Self::V1(b), DABatch::V1(parent)) => DABatch::V1(b.build(parent)?),
-            (Self::V2(b), DABatch::V1(parent)) => DABatch::V2(b.build(parent)?),
-            (Self::V2(b), DABatch::V2(parent)) => DABatch::V2(b.build(parent)?),
-            (Self::V3(b), DABatch::V2(parent)) => DABatch::V3(b.build(parent)?),
-            (Self::V3(b), DABatch::V3(parent)) => DABatch::V3(b.build(parent)?),
-            (Self::V4(b), DABatch::V3(parent)) => DABatch::V4(b.build(parent)?),
-            (Self::V4(b), DABatch::V4(parent)) => DABatch::V4(b.build(parent)?),
+            (Self::V0(b), DABatch::V0(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V0(batch), report)
+            }
+            (Self::V1(b), DABatch::V0(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V1(batch), report)
+            }
+            (Self::V1(b), DABatch::V1(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V1(batch), report)
+            }
+            (Self::V2(b), DABatch::V1(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V2(batch), report)
+            }
+            (Self::V2(b), DABatch::V2(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V2(batch), report)
+            }
+            (Self::V3(b), DABatch::V2(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V3(batch), report)
+            }
+            (Self::V3(b), DABatch::V3(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V3(batch), report)
+            }
+            (Self::V4(b), DABatch::V3(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V4(batch), report)
+            }
+            (Self::V4(b), DABatch::V4(parent)) => {
+                let (batch, report) = b.build_with_report(parent, settings)?;
+                (DABatch::V4(batch), report)
+            }
             (b, parent) => {
                 return Err(BatchError::MismatchBatchVersionAndBlock {
                     block_batch_version: b.version(),
@@ -91,13 +218,128 @@ impl BatchBuilder {
 }
 
 pub struct VersionedBatchBuilder<T: BatchVersionedType> {
-    numbers: Vec<Vec<u64>>,
+    slots: BlockSlots<T::Block>,
     _marker: PhantomData<T>,
+}
 
-    current_chunk_id: usize,
-    current_block_id: usize,
+/// Blocks placed into the chunks given by block numbers, in any order.
+#[derive(Debug)]
+pub(crate) struct BlockSlots<B> {
+    index: HashMap<u64, (usize, usize)>,
+    slots: Vec<Vec<Option<B>>>,
+    // block numbers listed more than once in the chunks
+    duplicated: Vec<u64>,
+}
 
-    pub chunks: Vec<T::Chunk>,
+impl<B> BlockSlots<B> {
+    pub(crate) fn new(numbers: &[Vec<u64>]) -> Self {
+        let mut index = HashMap::with_capacity(numbers.iter().map(Vec::len).sum());
+        let mut duplicated = Vec::new();
+        for (chunk_id, chunk) in numbers.iter().enumerate() {
+            for (block_id, number) in chunk.iter().enumerate() {
+                if index.insert(*number, (chunk_id, block_id)).is_some() {
+                    duplicated.push(*number);
+                }
+            }
+        }
+        Self {
+            index,
+            slots: numbers
+                .iter()
+                .map(|chunk| chunk.iter().map(|_| None).collect())
+                .collect(),
+            duplicated,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, number: u64, block: B) -> Result<(), BatchError> {
+        let (chunk_id, block_id) = *self
+            .index
+            .get(&number)
+            .ok_or(BatchError::UnknownBlock(number))?;
+        let slot = &mut self.slots[chunk_id][block_id];
+        if slot.is_some() {
+            return Err(BatchError::DuplicateBlock(number));
+        }
+        *slot = Some(block);
+        Ok(())
+    }
+
+    /// Block numbers not inserted yet, in chunk order.
+    pub(crate) fn missing(&self) -> Vec<u64> {
+        let mut missing: Vec<_> = self
+            .index
+            .iter()
+            .filter(|(_, (chunk_id, block_id))| self.slots[*chunk_id][*block_id].is_none())
+            .map(|(number, pos)| (*pos, *number))
+            .collect();
+        missing.sort();
+        missing.into_iter().map(|(_, number)| number).collect()
+    }
+
+    pub(crate) fn into_chunks(self) -> Result<Vec<Vec<B>>, BatchError> {
+        if let Some(number) = self.duplicated.first() {
+            return Err(BatchError::DuplicateBlock(*number));
+        }
+        let missing = self.missing();
+        if !missing.is_empty() {
+            return Err(BatchError::MissingBlocks(missing));
+        }
+        Ok(self
+            .slots
+            .into_iter()
+            .map(|chunk| chunk.into_iter().flatten().collect())
+            .collect())
+    }
+}
+
+/// The intermediate values of a built batch, for finding which part of a
+/// batch hash diverged from the one committed on L1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchBuildReport {
+    pub version: u8,
+    pub batch_hash: B256,
+    pub chunk_hashes: Vec<B256>,
+    pub chunk_l1_messages: Vec<u64>,
+    /// keccak256 of the chunk hashes
+    pub data_hash: B256,
+    /// None for v0, which has no blob
+    pub blob: Option<BlobPayloadReport>,
+}
+
+impl BatchBuildReport {
+    /// Hashes the chunks, the batch hash and the blob are filled in by
+    /// BatchTrait::new_with_report.
+    pub fn new<C: ChunkTrait>(
+        version: u8,
+        chunks: &[C],
+        total_l1_message_popped_before: u64,
+    ) -> Result<Self, BatchError> {
+        let mut chunk_hashes = Vec::with_capacity(chunks.len());
+        let mut chunk_l1_messages = Vec::with_capacity(chunks.len());
+        let mut total_l1_message_popped_before_chunk = total_l1_message_popped_before;
+        for chunk in chunks {
+            chunk_hashes.push(
+                chunk
+                    .hash(total_l1_message_popped_before_chunk)
+                    .map_err(BatchError::BuildChunkHash())?,
+            );
+            let num_l1_messages = chunk.num_l1_messages(total_l1_message_popped_before_chunk);
+            chunk_l1_messages.push(num_l1_messages);
+            total_l1_message_popped_before_chunk += num_l1_messages;
+        }
+        let data_bytes: Vec<u8> = chunk_hashes.iter().flat_map(|n| n.0).collect();
+        let data_hash = keccak256(&data_bytes);
+
+        Ok(Self {
+            version,
+            batch_hash: B256::default(),
+            chunk_hashes,
+            chunk_l1_messages,
+            data_hash,
+            blob: None,
+        })
+    }
 }
 
 pub trait BatchContext: Context {
@@ -108,11 +350,8 @@ pub trait BatchContext: Context {
 impl<T: BatchVersionedType> VersionedBatchBuilder<T> {
     pub fn new(numbers: Vec<Vec<u64>>) -> Self {
         Self {
-            chunks: Vec::with_capacity(numbers.len()),
-            numbers,
+            slots: BlockSlots::new(&numbers),
             _marker: PhantomData,
-            current_block_id: 0,
-            current_chunk_id: 0,
         }
     }
 
@@ -122,40 +361,170 @@ impl<T: BatchVersionedType> VersionedBatchBuilder<T> {
         for (tx_idx, tx) in ctx.txs().iter().enumerate() {
             txs.push(T::Tx::new(tx, ctx.tx_rlp(tx_idx)));
         }
-        let block = T::Block::new(ctx, txs);
-
-        for (chunk_id, chunk) in self.numbers.iter().enumerate() {
-            for (block_id, blkno) in chunk.iter().enumerate() {
-                if blkno == &block.number() {
-                    let mut expect_chunk_id = self.current_chunk_id;
-                    let mut expect_block_id = self.current_block_id;
-                    if expect_block_id == self.numbers[self.current_chunk_id].len() {
-                        expect_chunk_id += 1;
-                        expect_block_id = 0;
-                    }
-                    if expect_block_id != block_id || expect_chunk_id != chunk_id {
-                        return Err(BatchError::UnexpectedBlock {
-                            want: (expect_block_id, expect_chunk_id),
-                            got: (block_id, chunk_id),
-                        });
-                    }
-                    if block_id == 0 {
-                        self.chunks.push(T::Chunk::default());
-                    }
-                    let chunk = self.chunks.get_mut(chunk_id).unwrap();
-                    chunk.add_block(block);
-
-                    self.current_chunk_id = chunk_id;
-                    self.current_block_id = block_id + 1;
-                    return Ok(());
-                }
+        self.add_block(T::Block::new(ctx, txs))
+    }
+
+    /// Adds a block from its header fields and raw transactions.
+    pub fn add_raw(&mut self, header: &BlockHeaderInfo, txs: Vec<RawTx>) -> Result<(), BatchError> {
+        let txs = txs.into_iter().map(T::Tx::from_raw).collect();
+        self.add_block(T::Block::from_header(header, txs))
+    }
+
+    /// Blocks can be added in any order, each number at most once.
+    pub fn add_block(&mut self, block: T::Block) -> Result<(), BatchError> {
+        self.slots.insert(block.number(), block)
+    }
+
+    /// Block numbers still to be added.
+    pub fn missing_blocks(&self) -> Vec<u64> {
+        self.slots.missing()
+    }
+
+    pub fn chunks(self) -> Result<Vec<T::Chunk>, BatchError> {
+        let mut chunks = Vec::new();
+        for blocks in self.slots.into_chunks()? {
+            let mut chunk = T::Chunk::default();
+            for block in blocks {
+                chunk.add_block(block);
             }
+            chunks.push(chunk);
         }
-
-        Err(BatchError::UnknownBlock)
+        Ok(chunks)
     }
 
     pub fn build<B: BatchTrait>(self, parent: B) -> Result<T::Batch, BatchError> {
-        T::Batch::new(parent, self.chunks)
+        self.build_with_settings(parent, &BUILDIN_TRUSTED_SETTING)
+    }
+
+    pub fn build_with_settings<B: BatchTrait>(
+        self,
+        parent: B,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<T::Batch, BatchError> {
+        Ok(self.build_with_report(parent, settings)?.0)
+    }
+
+    pub fn build_with_report<B: BatchTrait>(
+        self,
+        parent: B,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(T::Batch, BatchBuildReport), BatchError> {
+        T::Batch::new_with_report(parent, self.chunks()?, settings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_block_slots() {
+        let mut slots = BlockSlots::new(&[vec![1, 2], vec![3, 4]]);
+        slots.insert(4, "d").unwrap();
+        slots.insert(2, "b").unwrap();
+        assert!(matches!(
+            slots.insert(2, "b"),
+            Err(BatchError::DuplicateBlock(2))
+        ));
+        assert!(matches!(
+            slots.insert(5, "e"),
+            Err(BatchError::UnknownBlock(5))
+        ));
+        assert_eq!(slots.missing(), vec![1, 3]);
+
+        slots.insert(3, "c").unwrap();
+        slots.insert(1, "a").unwrap();
+        assert_eq!(
+            slots.into_chunks().unwrap(),
+            vec![vec!["a", "b"], vec!["c", "d"]]
+        );
+
+        let slots = BlockSlots::<()>::new(&[vec![1, 2], vec![2]]);
+        assert!(matches!(
+            slots.into_chunks(),
+            Err(BatchError::DuplicateBlock(2))
+        ));
+        let slots = BlockSlots::<()>::new(&[vec![1], vec![2]]);
+        assert!(matches!(
+            slots.into_chunks(),
+            Err(BatchError::MissingBlocks(n)) if n == vec![1, 2]
+        ));
+    }
+
+    #[test]
+    fn test_with_numbers_version() {
+        use crate::{v1, v2};
+
+        let parent = DABatch::V1(v1::DABatch {
+            version: v1::VERSION,
+            ..Default::default()
+        });
+        for (version, ok) in [(0, false), (1, true), (2, true), (3, false)] {
+            let builder = BatchBuilder::with_numbers(version, &parent, vec![vec![1]]);
+            assert_eq!(builder.is_ok(), ok, "version {}", version);
+        }
+        let parent = DABatch::V2(v2::DABatch {
+            version: v2::VERSION,
+            ..Default::default()
+        });
+        // the hardfork config is behind the parent, the parent's version is built
+        let builder = BatchBuilder::with_numbers(v1::VERSION, &parent, vec![vec![1]]).unwrap();
+        assert_eq!(builder.version(), v2::VERSION);
+        assert!(matches!(
+            BatchBuilder::with_numbers(v0::VERSION, &parent, vec![vec![1]]),
+            Err(BatchError::MismatchBatchVersionAndBlock {
+                block_batch_version: 0,
+                parent_batch_version: 2,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_build_report() {
+        use crate::v4;
+
+        let parent = DABatch::V4(v4::DABatch {
+            version: v4::VERSION,
+            total_l1_message_popped: 2,
+            ..Default::default()
+        });
+        let mut builder =
+            BatchBuilder::with_numbers(v4::VERSION, &parent, vec![vec![1], vec![2]]).unwrap();
+        for number in [1, 2] {
+            let header = BlockHeaderInfo {
+                number,
+                ..Default::default()
+            };
+            let mut txs = vec![RawTx::l2(vec![0xc2, 0x01, number as u8])];
+            if number == 2 {
+                txs.insert(0, RawTx::l1_message(3, vec![0x7e, 0xc1, 0x03]));
+            }
+            builder.add_raw(&header, txs).unwrap();
+        }
+        let (batch, report) = builder
+            .build_with_report(parent, &BUILDIN_TRUSTED_SETTING)
+            .unwrap();
+
+        assert_eq!(report.batch_hash, batch.hash());
+        assert_eq!(report.data_hash, batch.data_hash());
+        assert_eq!(report.chunk_hashes.len(), 2);
+        let data_bytes: Vec<u8> = report.chunk_hashes.iter().flat_map(|n| n.0).collect();
+        assert_eq!(report.data_hash, keccak256(&data_bytes));
+        assert_eq!(report.chunk_l1_messages, vec![0, 2]);
+        let blob = report.blob.unwrap();
+        assert_eq!(Some(blob.blob_versioned_hash), batch.blob_versioned_hash());
+        assert_eq!(batch.blob_data_proof(), Some([blob.z, blob.y]));
+        assert_eq!(blob.challenge_preimage.len(), (v4::MAX_NUM_CHUNKS + 2) * 32);
+
+        // metadata: 2 chunks of 3 bytes each, then the L2 transactions
+        let mut payload = vec![0_u8; 2 + v4::MAX_NUM_CHUNKS * 4];
+        payload[..10].copy_from_slice(&[0, 2, 0, 0, 0, 3, 0, 0, 0, 3]);
+        payload.extend_from_slice(&[0xc2, 0x01, 0x01, 0xc2, 0x01, 0x02]);
+        let compressed = crate::compress_scroll_batch_bytes(&payload).unwrap();
+        assert_eq!(blob.outcome, crate::BlobCompressOutcome::Compressed);
+        assert_eq!(blob.uncompressed_size, payload.len());
+        // the v4 compress flag comes first
+        assert_eq!(blob.compressed_size, 1 + compressed.len());
+        assert!(blob.compressed_size < blob.uncompressed_size);
     }
 }
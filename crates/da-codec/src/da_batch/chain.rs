@@ -0,0 +1,186 @@
+use super::{BatchError, DABatch, BATCH_BUILDER_VERSIONS};
+
+/// Whether BatchBuilder builds a batch of `version` on a parent of
+/// `parent_version`, see BATCH_BUILDER_VERSIONS.
+pub fn is_valid_version_transition(parent_version: u8, version: u8) -> bool {
+    BATCH_BUILDER_VERSIONS
+        .iter()
+        .any(|&((_, parent), built)| (parent, built) == (parent_version, version))
+}
+
+/// Checks that `batch` directly follows `parent`.
+pub fn check_batch_link(parent: &DABatch, batch: &DABatch) -> Result<(), BatchError> {
+    let batch_index = batch.batch_index();
+    if batch_index != parent.batch_index() + 1 {
+        return Err(BatchError::NonContiguousBatchIndex {
+            want: parent.batch_index() + 1,
+            got: batch_index,
+        });
+    }
+
+    if !is_valid_version_transition(parent.version(), batch.version()) {
+        return Err(BatchError::InvalidBatchVersionTransition {
+            batch_index,
+            parent_version: parent.version(),
+            version: batch.version(),
+        });
+    }
+
+    let want = parent.hash();
+    if batch.parent_batch_hash() != want {
+        return Err(BatchError::ParentBatchHashMismatch {
+            batch_index,
+            want,
+            got: batch.parent_batch_hash(),
+        });
+    }
+
+    let want = parent.total_l1_message_popped() + batch.l1_message_popped();
+    if batch.total_l1_message_popped() != want {
+        return Err(BatchError::TotalL1MessagePoppedMismatch {
+            batch_index,
+            want,
+            got: batch.total_l1_message_popped(),
+        });
+    }
+    Ok(())
+}
+
+/// Validates a run of batch headers pushed in order, stops at the first break.
+#[derive(Debug, Clone, Default)]
+pub struct BatchChainValidator {
+    last: Option<DABatch>,
+    num_batches: usize,
+}
+
+impl BatchChainValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from a trusted header, the first pushed batch must be its child.
+    pub fn with_parent(parent: DABatch) -> Self {
+        Self {
+            last: Some(parent),
+            num_batches: 0,
+        }
+    }
+
+    pub fn push(&mut self, batch: DABatch) -> Result<(), BatchError> {
+        if let Some(parent) = &self.last {
+            check_batch_link(parent, &batch)?;
+        }
+        self.last = Some(batch);
+        self.num_batches += 1;
+        Ok(())
+    }
+
+    pub fn validate<I: IntoIterator<Item = DABatch>>(
+        &mut self,
+        batches: I,
+    ) -> Result<(), BatchError> {
+        for batch in batches {
+            self.push(batch)?;
+        }
+        Ok(())
+    }
+
+    pub fn last(&self) -> Option<&DABatch> {
+        self.last.as_ref()
+    }
+
+    /// The number of batches accepted, excluding the trusted parent.
+    pub fn num_batches(&self) -> usize {
+        self.num_batches
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{v2, v3};
+
+    fn child(parent: &DABatch, l1_message_popped: u64) -> DABatch {
+        DABatch::V3(v3::DABatch {
+            version: v3::VERSION,
+            batch_index: parent.batch_index() + 1,
+            l1_message_popped,
+            total_l1_message_popped: parent.total_l1_message_popped() + l1_message_popped,
+            parent_batch_hash: parent.hash(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_batch_chain_validator() {
+        let genesis = DABatch::V2(v2::DABatch {
+            version: v2::VERSION,
+            batch_index: 10,
+            total_l1_message_popped: 5,
+            ..Default::default()
+        });
+        let b1 = child(&genesis, 3);
+        let b2 = child(&b1, 0);
+
+        let mut validator = BatchChainValidator::new();
+        validator
+            .validate([genesis.clone(), b1.clone(), b2.clone()])
+            .unwrap();
+        assert_eq!(validator.num_batches(), 3);
+
+        let mut broken = b2.clone();
+        if let DABatch::V3(b) = &mut broken {
+            b.total_l1_message_popped += 1;
+        }
+        let mut validator = BatchChainValidator::with_parent(b1.clone());
+        assert!(matches!(
+            validator.push(broken),
+            Err(BatchError::TotalL1MessagePoppedMismatch {
+                batch_index: 12,
+                ..
+            })
+        ));
+
+        let mut validator = BatchChainValidator::with_parent(b2.clone());
+        assert!(matches!(
+            validator.push(b1.clone()),
+            Err(BatchError::NonContiguousBatchIndex { want: 13, got: 11 })
+        ));
+
+        let mut validator = BatchChainValidator::with_parent(genesis.clone());
+        assert!(matches!(
+            validator.push(child(&b1, 0)),
+            Err(BatchError::NonContiguousBatchIndex { want: 11, got: 12 })
+        ));
+
+        let mut validator = BatchChainValidator::with_parent(b1.clone());
+        let mut forked = b2.clone();
+        if let DABatch::V3(b) = &mut forked {
+            b.parent_batch_hash = genesis.hash();
+        }
+        assert!(matches!(
+            validator.push(forked),
+            Err(BatchError::ParentBatchHashMismatch {
+                batch_index: 12,
+                ..
+            })
+        ));
+
+        let downgrade = DABatch::V2(v2::DABatch {
+            version: v2::VERSION,
+            batch_index: 12,
+            total_l1_message_popped: 8,
+            parent_batch_hash: b1.hash(),
+            ..Default::default()
+        });
+        assert!(matches!(
+            validator.push(downgrade),
+            Err(BatchError::InvalidBatchVersionTransition {
+                parent_version: 3,
+                version: 2,
+                ..
+            })
+        ));
+        validator.push(b2).unwrap();
+    }
+}
@@ -1,6 +1,18 @@
 mod builder;
 pub use builder::*;
 
+mod block_info;
+pub use block_info::*;
+
+mod proposer;
+pub use proposer::*;
+
+mod chain;
+pub use chain::*;
+
+mod repr;
+pub use repr::da_batch_hex;
+
 pub mod v0;
 pub mod v1;
 pub mod v2;
@@ -10,6 +22,7 @@ pub mod v4;
 pub(crate) mod prelude {
     pub use super::super::{BatchError, BatchVersionedType};
     pub use super::utils::*;
+    pub use super::{BatchBuildReport, BlockHeaderInfo, RawTx};
     pub use scroll_executor::{revm::primitives::keccak256, B256, U256, Transaction};
     pub use serde::{Deserialize, Serialize};
     #[cfg(test)]
@@ -18,16 +31,25 @@ pub(crate) mod prelude {
 
 use prelude::*;
 mod utils;
+pub use utils::{
+    BatchTrait, BlobCompressOutcome, BlobCompressPolicy, BlobPayload, BlobPayloadCompress,
+    BlobPayloadReport, BlobPayloadSize, BlockTrait, ChunkTrait, DecodedBlobPayload, TxTrait,
+    MAX_BLOB_PAYLOAD_SIZE, MAX_DECOMPRESSED_PAYLOAD_SIZE,
+};
+pub use utils::{decode_skipped_bitmap, L1MessageQueueRanges};
+pub(crate) use utils::blob_codec_params;
 
 pub trait BatchVersionedType {
+    const VERSION: u8;
     type Batch: BatchTrait;
     type Chunk: ChunkTrait<Block = Self::Block>;
     type Block: BlockTrait<Tx = Self::Tx>;
     type Tx: TxTrait;
 }
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(untagged)]
+/// Serialized as a flat object whose `version` selects the variant, see
+/// da_batch_hex for the compact form.
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
 pub enum DABatch {
     V0(v0::DABatch),
     V1(v1::DABatch),
@@ -36,6 +58,22 @@ pub enum DABatch {
     V4(v4::DABatch),
 }
 
+/// The fields of a batch header regardless of its version, None when the
+/// version doesn't have the field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DABatchView {
+    pub version: u8,
+    pub batch_index: u64,
+    pub batch_hash: B256,
+    pub l1_message_popped: u64,
+    pub total_l1_message_popped: u64,
+    pub data_hash: B256,
+    pub parent_batch_hash: B256,
+    pub blob_versioned_hash: Option<B256>,
+    pub last_block_timestamp: Option<u64>,
+    pub blob_data_proof: Option<[B256; 2]>,
+    pub skipped_l1_message_bitmap: Option<Vec<u8>>,
+}
 
 impl DABatch {
     pub fn total_l1_message_popped(&self) -> u64 {
@@ -48,6 +86,26 @@ impl DABatch {
         }
     }
 
+    pub fn l1_message_popped(&self) -> u64 {
+        match self {
+            Self::V0(b) => b.l1_message_popped,
+            Self::V1(b) => b.l1_message_popped,
+            Self::V2(b) => b.l1_message_popped,
+            Self::V3(b) => b.l1_message_popped,
+            Self::V4(b) => b.l1_message_popped,
+        }
+    }
+
+    pub fn parent_batch_hash(&self) -> B256 {
+        match self {
+            Self::V0(b) => b.parent_batch_hash,
+            Self::V1(b) => b.parent_batch_hash,
+            Self::V2(b) => b.parent_batch_hash,
+            Self::V3(b) => b.parent_batch_hash,
+            Self::V4(b) => b.parent_batch_hash,
+        }
+    }
+
     pub fn version(&self) -> u8 {
         match self {
             Self::V0(b) => b.version,
@@ -58,6 +116,24 @@ impl DABatch {
         }
     }
 
+    /// Checks that the inner version matches the variant.
+    pub fn check_version(&self) -> Result<(), BatchError> {
+        let variant = match self {
+            Self::V0(_) => v0::VERSION,
+            Self::V1(_) => v1::VERSION,
+            Self::V2(_) => v2::VERSION,
+            Self::V3(_) => v3::VERSION,
+            Self::V4(_) => v4::VERSION,
+        };
+        if self.version() != variant {
+            return Err(BatchError::BatchVersionMismatch {
+                variant,
+                version: self.version(),
+            });
+        }
+        Ok(())
+    }
+
     pub fn batch_index(&self) -> u64 {
         match self {
             Self::V0(b) => b.batch_index,
@@ -68,6 +144,99 @@ impl DABatch {
         }
     }
 
+    /// None for v0, which carries its data in calldata.
+    pub fn blob_versioned_hash(&self) -> Option<B256> {
+        match self {
+            Self::V0(_) => None,
+            Self::V1(b) => Some(b.blob_versioned_hash),
+            Self::V2(b) => Some(b.blob_versioned_hash),
+            Self::V3(b) => Some(b.blob_versioned_hash),
+            Self::V4(b) => Some(b.blob_versioned_hash),
+        }
+    }
+
+    pub fn data_hash(&self) -> B256 {
+        match self {
+            Self::V0(b) => b.data_hash,
+            Self::V1(b) => b.data_hash,
+            Self::V2(b) => b.data_hash,
+            Self::V3(b) => b.data_hash,
+            Self::V4(b) => b.data_hash,
+        }
+    }
+
+    /// None for v3 and later, which pass the bitmap in the commit calldata.
+    pub fn skipped_l1_message_bitmap(&self) -> Option<&[u8]> {
+        match self {
+            Self::V0(b) => Some(&b.skipped_l1_message_bitmap),
+            Self::V1(b) => Some(&b.skipped_l1_message_bitmap),
+            Self::V2(b) => Some(&b.skipped_l1_message_bitmap),
+            Self::V3(_) | Self::V4(_) => None,
+        }
+    }
+
+    /// Added in v3.
+    pub fn last_block_timestamp(&self) -> Option<u64> {
+        match self {
+            Self::V0(_) | Self::V1(_) | Self::V2(_) => None,
+            Self::V3(b) => Some(b.last_block_timestamp),
+            Self::V4(b) => Some(b.last_block_timestamp),
+        }
+    }
+
+    /// [z, y], added in v3.
+    pub fn blob_data_proof(&self) -> Option<[B256; 2]> {
+        match self {
+            Self::V0(_) | Self::V1(_) | Self::V2(_) => None,
+            Self::V3(b) => Some(b.blob_data_proof),
+            Self::V4(b) => Some(b.blob_data_proof),
+        }
+    }
+
+    pub fn view(&self) -> DABatchView {
+        DABatchView {
+            version: self.version(),
+            batch_index: self.batch_index(),
+            batch_hash: self.hash(),
+            l1_message_popped: self.l1_message_popped(),
+            total_l1_message_popped: self.total_l1_message_popped(),
+            data_hash: self.data_hash(),
+            parent_batch_hash: self.parent_batch_hash(),
+            blob_versioned_hash: self.blob_versioned_hash(),
+            last_block_timestamp: self.last_block_timestamp(),
+            blob_data_proof: self.blob_data_proof(),
+            skipped_l1_message_bitmap: self.skipped_l1_message_bitmap().map(|n| n.to_vec()),
+        }
+    }
+
+    /// The skipped and included L1 queue indices of this batch. v3 and later
+    /// moved the bitmap out of the header, see CommitBatchCall::skipped_l1_message_bitmap
+    /// and decode_skipped_bitmap.
+    pub fn l1_message_queue_ranges(
+        &self,
+        parent_total_l1_message_popped: u64,
+    ) -> Result<L1MessageQueueRanges, BatchError> {
+        let bitmap = self
+            .skipped_l1_message_bitmap()
+            .ok_or(BatchError::MissingSkippedBitmap {
+                version: self.version(),
+            })?;
+        let want = parent_total_l1_message_popped + self.l1_message_popped();
+        if self.total_l1_message_popped() != want {
+            return Err(BatchError::TotalL1MessagePoppedMismatch {
+                batch_index: self.batch_index(),
+                want,
+                got: self.total_l1_message_popped(),
+            });
+        }
+        decode_skipped_bitmap(
+            self.version(),
+            bitmap,
+            parent_total_l1_message_popped,
+            self.l1_message_popped(),
+        )
+    }
+
     pub fn hash(&self) -> B256 {
         match self {
             Self::V0(b) => b.hash(),
@@ -88,8 +257,38 @@ impl DABatch {
         }
     }
 
+    /// Checks the blob_versioned_hash and blob_data_proof in the header against a
+    /// published blob, with the commitment and the proof from its sidecar.
+    pub fn verify_blob(
+        &self,
+        blob: &c_kzg::Blob,
+        commitment: &c_kzg::Bytes48,
+        proof: &c_kzg::Bytes48,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(), BatchError> {
+        let (blob_versioned_hash, blob_data_proof) =
+            match (self.blob_versioned_hash(), self.blob_data_proof()) {
+                (Some(hash), Some(proof)) => (hash, proof),
+                _ => {
+                    return Err(BatchError::MissingBlobDataProof {
+                        version: self.version(),
+                    })
+                }
+            };
+        BlobPayload::verify_blob_data_proof(
+            self.version(),
+            blob,
+            commitment,
+            proof,
+            blob_versioned_hash,
+            blob_data_proof,
+            settings,
+        )
+    }
+
     pub fn from_bytes(data: &[u8]) -> Result<Self, BatchError> {
-        Ok(match data[0] {
+        let version = *data.first().ok_or(BatchError::EmptyBatchHeader)?;
+        Ok(match version {
             0 => Self::V0(v0::DABatch::from_bytes(data)?),
             1 => Self::V1(v1::DABatch::from_bytes(data)?),
             2 => Self::V2(v2::DABatch::from_bytes(data)?),
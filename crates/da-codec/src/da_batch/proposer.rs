@@ -0,0 +1,413 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use super::{
+    blob_codec_params, BatchContext, BatchError, BatchTrait, BatchVersionedType,
+    BlobCompressOutcome, BlobCompressPolicy, BlobPayload, BlobPayloadCompress, BlockTrait,
+    ChunkTrait, TxTrait, MAX_BLOB_PAYLOAD_SIZE,
+};
+
+/// the number of blocks in a chunk is encoded as a single byte
+pub const MAX_BLOCKS_PER_CHUNK: usize = 255;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchProposerConfig {
+    /// capped at MAX_BLOCKS_PER_CHUNK
+    pub max_blocks_per_chunk: usize,
+    /// capped at the MAX_NUM_CHUNKS of the codec
+    pub max_chunks_per_batch: usize,
+    /// used to estimate ZstdV4 payloads, RequireCompatible rejects a single
+    /// block whose compressed data is incompatible
+    pub compress_policy: BlobCompressPolicy,
+}
+
+impl Default for BatchProposerConfig {
+    fn default() -> Self {
+        Self {
+            max_blocks_per_chunk: MAX_BLOCKS_PER_CHUNK,
+            max_chunks_per_batch: usize::MAX,
+            compress_policy: BlobCompressPolicy::default(),
+        }
+    }
+}
+
+pub struct ProposedBatch<T: BatchVersionedType> {
+    pub chunks: Vec<T::Chunk>,
+}
+
+impl<T: BatchVersionedType> ProposedBatch<T> {
+    /// Block numbers grouped by chunk, the same shape as BatchTask::chunks.
+    pub fn block_numbers(&self) -> Vec<Vec<u64>> {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.blocks().iter().map(|blk| blk.number()).collect())
+            .collect()
+    }
+
+    pub fn build<B: BatchTrait>(self, parent: B) -> Result<T::Batch, BatchError> {
+        T::Batch::new(parent, self.chunks)
+    }
+
+    pub fn build_with_settings<B: BatchTrait>(
+        self,
+        parent: B,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<T::Batch, BatchError> {
+        T::Batch::new_with_settings(parent, self.chunks, settings)
+    }
+}
+
+/// Splits an ordered block stream into chunks and batches under the codec limits.
+pub struct BatchProposer<T: BatchVersionedType> {
+    max_blocks_per_chunk: usize,
+    max_chunks_per_batch: usize,
+    blob: Option<(usize, BlobPayloadCompress)>,
+    compress_policy: BlobCompressPolicy,
+
+    next_block: Option<u64>,
+    total_l1_message_popped: u64,
+
+    /// the L2 tx bytes of the pending chunks and of the current chunk, the
+    /// raw payload size without building it
+    chunks_size: usize,
+    current_chunk_size: usize,
+    current_chunk: T::Chunk,
+    chunks: Vec<T::Chunk>,
+    batches: VecDeque<ProposedBatch<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: BatchVersionedType> BatchProposer<T> {
+    pub fn new(config: BatchProposerConfig, total_l1_message_popped: u64) -> Self {
+        // v0 carries everything in calldata and has no blob limits
+        let blob = blob_codec_params(T::VERSION).ok();
+        let max_chunks_per_batch = match blob {
+            Some((max_chunks, _)) => config.max_chunks_per_batch.min(max_chunks),
+            None => config.max_chunks_per_batch,
+        };
+        Self {
+            max_blocks_per_chunk: config.max_blocks_per_chunk.min(MAX_BLOCKS_PER_CHUNK),
+            max_chunks_per_batch: max_chunks_per_batch.max(1),
+            blob,
+            compress_policy: config.compress_policy,
+            next_block: None,
+            total_l1_message_popped,
+            chunks_size: 0,
+            current_chunk_size: 0,
+            current_chunk: T::Chunk::default(),
+            chunks: Vec::new(),
+            batches: VecDeque::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn add<C: BatchContext>(&mut self, ctx: &C) -> Result<(), BatchError> {
+        let mut txs = Vec::new();
+        for (tx_idx, tx) in ctx.txs().iter().enumerate() {
+            txs.push(T::Tx::new(tx, ctx.tx_rlp(tx_idx)));
+        }
+        self.add_block(T::Block::new(ctx, txs))
+    }
+
+    pub fn add_block(&mut self, block: T::Block) -> Result<(), BatchError> {
+        if let Some(want) = self.next_block {
+            if block.number() != want {
+                return Err(BatchError::UnexpectedBlockNumber {
+                    want,
+                    got: block.number(),
+                });
+            }
+        }
+
+        // the limits enforced by the 60-byte block context, skipped L1 messages
+        // are counted as well
+        let num_l1_messages = block.num_l1_messages(self.total_l1_message_popped);
+        if num_l1_messages > u16::MAX as u64 {
+            return Err(BatchError::NumL1TxTooLarge);
+        }
+        let num_l2_txs = block.txs().iter().filter(|tx| !tx.is_l1_msg()).count();
+        if num_l1_messages + num_l2_txs as u64 > u16::MAX as u64 {
+            return Err(BatchError::NumTxTooLarge);
+        }
+
+        let block_size = l2_tx_size(&block);
+        let (close_chunk, close_batch) = self.propose(&block, block_size)?;
+        if close_chunk {
+            self.close_chunk();
+        }
+        if close_batch {
+            self.close_batch();
+        }
+
+        self.next_block = Some(block.number() + 1);
+        self.total_l1_message_popped += num_l1_messages;
+        self.current_chunk_size += block_size;
+        self.current_chunk.add_block(block);
+        Ok(())
+    }
+
+    /// Returns the next batch which can't take any more blocks.
+    pub fn pop_batch(&mut self) -> Option<ProposedBatch<T>> {
+        self.batches.pop_front()
+    }
+
+    /// Closes the pending chunk and batch, returns every batch not popped yet.
+    pub fn flush(&mut self) -> Vec<ProposedBatch<T>> {
+        self.close_chunk();
+        self.close_batch();
+        self.batches.drain(..).collect()
+    }
+
+    fn close_chunk(&mut self) {
+        if self.current_chunk.blocks().is_empty() {
+            return;
+        }
+        self.chunks.push(std::mem::take(&mut self.current_chunk));
+        self.chunks_size += std::mem::take(&mut self.current_chunk_size);
+        if self.chunks.len() >= self.max_chunks_per_batch {
+            self.close_batch();
+        }
+    }
+
+    fn close_batch(&mut self) {
+        if self.chunks.is_empty() {
+            return;
+        }
+        self.batches.push_back(ProposedBatch {
+            chunks: std::mem::take(&mut self.chunks),
+        });
+        self.chunks_size = 0;
+    }
+
+    /// Decides whether the current chunk and the pending batch have to be
+    /// closed before taking the block, without changing any state: a
+    /// rejected block leaves the proposer as it was.
+    fn propose(&self, block: &T::Block, block_size: usize) -> Result<(bool, bool), BatchError> {
+        let mut close_chunk = self.current_chunk.blocks().len() >= self.max_blocks_per_chunk;
+        let size = self.chunks_size + self.current_chunk_size + block_size;
+        if !close_chunk && self.fits_uncompressed(size) {
+            return Ok((false, false));
+        }
+
+        let current_empty = self.current_chunk.blocks().is_empty();
+        let mut chunk = self.current_chunk.clone();
+        chunk.add_block(block.clone());
+        if !close_chunk {
+            // the common case, the block joins the current chunk and batch
+            let mut batch = self.chunks.clone();
+            batch.push(chunk.clone());
+            if self.accept(&batch)? {
+                return Ok((false, false));
+            }
+            // without pending chunks, the chunk alone was just rejected
+            close_chunk = !current_empty
+                && (self.chunks.is_empty() || !self.accept(std::slice::from_ref(&chunk))?);
+        }
+
+        let mut batch = self.chunks.clone();
+        if close_chunk {
+            chunk = T::Chunk::default();
+            chunk.add_block(block.clone());
+            if !current_empty {
+                batch.push(self.current_chunk.clone());
+            }
+        }
+        // closing the chunk closes a full batch as well
+        let mut close_batch = close_chunk && batch.len() >= self.max_chunks_per_batch;
+        if !close_batch && !batch.is_empty() {
+            batch.push(chunk.clone());
+            // with the current chunk open, this is the batch rejected above
+            close_batch = !close_chunk || !self.accept(&batch)?;
+        }
+        if (close_batch || batch.is_empty()) && !self.accept(std::slice::from_ref(&chunk))? {
+            return Err(BatchError::OversizedBlock {
+                number: block.number(),
+            });
+        }
+        Ok((close_chunk, close_batch))
+    }
+
+    /// Whether a batch with `size` bytes of L2 txs fits a blob whatever
+    /// the compression does, so it doesn't need to be compressed.
+    fn fits_uncompressed(&self, size: usize) -> bool {
+        let (max_chunks, compress) = match self.blob {
+            Some(blob) => blob,
+            None => return true,
+        };
+        let size = 2 + max_chunks * 4 + size;
+        let size = match (compress, self.compress_policy) {
+            (BlobPayloadCompress::None, _) => size,
+            (BlobPayloadCompress::Zstd, _) => zstd_compress_bound(size),
+            (BlobPayloadCompress::ZstdV4, BlobCompressPolicy::Never) => size + 1,
+            (BlobPayloadCompress::ZstdV4, BlobCompressPolicy::Always) => {
+                zstd_compress_bound(size) + 1
+            }
+            // the compressed data has to pass the compatibility check
+            (BlobPayloadCompress::ZstdV4, _) => return false,
+        };
+        size <= MAX_BLOB_PAYLOAD_SIZE
+    }
+
+    fn accept(&self, chunks: &[T::Chunk]) -> Result<bool, BatchError> {
+        if chunks.len() > self.max_chunks_per_batch {
+            return Ok(false);
+        }
+        let (max_chunks, compress) = match self.blob {
+            Some(blob) => blob,
+            None => return Ok(true),
+        };
+
+        // an incompatible batch is split up first, before failing on a single block
+        let policy = match self.compress_policy {
+            BlobCompressPolicy::RequireCompatible => BlobCompressPolicy::IfCompatible,
+            policy => policy,
+        };
+        let size = BlobPayload::estimate_size_with_policy(chunks, max_chunks, compress, policy)?;
+        if !size.fits() {
+            return Ok(false);
+        }
+
+        match size.outcome {
+            // a batch whose compressed data is incompatible is committed uncompressed,
+            // it should contain only 1 chunk which contains only 1 block
+            // https://github.com/scroll-tech/scroll/blob/ae8c858a071c84647f921d91aa8fdee320f6c433/rollup/internal/controller/watcher/batch_proposer.go#L175
+            BlobCompressOutcome::Incompatible(err) => {
+                let single_block = matches!(chunks, [chunk] if chunk.blocks().len() == 1);
+                if single_block && self.compress_policy == BlobCompressPolicy::RequireCompatible {
+                    return Err(err.into());
+                }
+                Ok(single_block)
+            }
+            _ => Ok(true),
+        }
+    }
+}
+
+fn l2_tx_size<B: BlockTrait>(block: &B) -> usize {
+    block
+        .txs()
+        .iter()
+        .filter(|tx| !tx.is_l1_msg())
+        .map(|tx| tx.rlp_bytes().len())
+        .sum()
+}
+
+/// ZSTD_COMPRESSBOUND, the worst case size of the compressed data, the
+/// scroll encoder omits the 4-byte magic number on top of it.
+fn zstd_compress_bound(size: usize) -> usize {
+    let small = if size < 128 << 10 {
+        ((128 << 10) - size) >> 11
+    } else {
+        0
+    };
+    size + (size >> 8) + small
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{v0, v2, v3};
+
+    fn test_block(number: u64, rlp: Vec<u8>) -> v0::DABlock {
+        v0::DABlock {
+            number,
+            txs: vec![v0::DABlockTx {
+                l1_msg: false,
+                nonce: number,
+                tx_hash: scroll_executor::revm::primitives::keccak256(&rlp),
+                rlp,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_propose_max_blocks_per_chunk() {
+        let config = BatchProposerConfig {
+            max_blocks_per_chunk: 2,
+            max_chunks_per_batch: 2,
+            ..Default::default()
+        };
+        let mut proposer = BatchProposer::<v3::CodecV3>::new(config, 0);
+        for number in 10..15 {
+            proposer
+                .add_block(test_block(number, vec![0xc1, 0x01]))
+                .unwrap();
+        }
+        assert!(proposer
+            .add_block(test_block(16, vec![0xc1, 0x01]))
+            .is_err());
+
+        let batch = proposer.pop_batch().unwrap();
+        assert_eq!(batch.block_numbers(), vec![vec![10, 11], vec![12, 13]]);
+        let batches = proposer.flush();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].block_numbers(), vec![vec![14]]);
+    }
+
+    #[test]
+    fn test_propose_blob_limit() {
+        // incompressible payloads, 4 of them fit into a blob
+        let mut seed = 1_u32;
+        let mut proposer = BatchProposer::<v2::CodecV2>::new(Default::default(), 0);
+        for number in 0..5 {
+            let mut rlp = vec![0xf9, 0x75, 0x30];
+            for _ in 0..30000 {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                rlp.push((seed >> 16) as u8);
+            }
+            proposer.add_block(test_block(number, rlp)).unwrap();
+        }
+
+        let batch = proposer.pop_batch().unwrap();
+        assert_eq!(batch.block_numbers(), vec![vec![0, 1, 2, 3]]);
+        let batches = proposer.flush();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].block_numbers(), vec![vec![4]]);
+    }
+
+    #[test]
+    fn test_propose_rejected_block() {
+        let mut proposer = BatchProposer::<v3::CodecV3>::new(Default::default(), 0);
+        proposer.add_block(test_block(0, vec![0xc1, 0x01])).unwrap();
+
+        // incompressible and larger than a blob
+        let mut seed = 1_u32;
+        let mut rlp = vec![0xfa, 0x01, 0xfb, 0xd0];
+        for _ in 0..130000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            rlp.push((seed >> 16) as u8);
+        }
+        assert!(matches!(
+            proposer.add_block(test_block(1, rlp)),
+            Err(BatchError::OversizedBlock { number: 1 })
+        ));
+
+        // 65535 L1 messages, the skipped ones included, and an L2 tx
+        let mut block = test_block(1, vec![0xc1, 0x01]);
+        block.txs.insert(
+            0,
+            v0::DABlockTx {
+                l1_msg: true,
+                nonce: 65534,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            proposer.add_block(block.clone()),
+            Err(BatchError::NumTxTooLarge)
+        ));
+        block.txs[0].nonce = 65535;
+        assert!(matches!(
+            proposer.add_block(block),
+            Err(BatchError::NumL1TxTooLarge)
+        ));
+
+        // the rejected blocks didn't close the chunk
+        proposer.add_block(test_block(1, vec![0xc1, 0x01])).unwrap();
+        assert!(proposer.pop_batch().is_none());
+        let batches = proposer.flush();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].block_numbers(), vec![vec![0, 1]]);
+    }
+}
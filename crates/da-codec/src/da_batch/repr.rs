@@ -0,0 +1,274 @@
+use base::eth::primitives::Bytes;
+use serde::{Deserializer, Serializer};
+
+use super::prelude::*;
+use super::{v0, v1, v2, v3, v4, DABatch};
+
+// The serde form of DABatch: a flat object whose `version` selects the variant.
+// Fields that don't exist in that version must be absent.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DABatchRepr {
+    version: u8,
+    batch_index: u64,
+    l1_message_popped: u64,
+    total_l1_message_popped: u64,
+    data_hash: B256,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blob_versioned_hash: Option<B256>,
+    parent_batch_hash: B256,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_bitmap"
+    )]
+    skipped_l1_message_bitmap: Option<Bytes>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_block_timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blob_data_proof: Option<[B256; 2]>,
+}
+
+impl DABatchRepr {
+    fn new(batch: &DABatch) -> Self {
+        Self {
+            version: batch.version(),
+            batch_index: batch.batch_index(),
+            l1_message_popped: batch.l1_message_popped(),
+            total_l1_message_popped: batch.total_l1_message_popped(),
+            data_hash: batch.data_hash(),
+            blob_versioned_hash: batch.blob_versioned_hash(),
+            parent_batch_hash: batch.parent_batch_hash(),
+            skipped_l1_message_bitmap: batch.skipped_l1_message_bitmap().map(|n| n.to_vec().into()),
+            last_block_timestamp: batch.last_block_timestamp(),
+            blob_data_proof: batch.blob_data_proof(),
+        }
+    }
+
+    fn into_batch(self) -> Result<DABatch, String> {
+        let version = self.version;
+        if version > v4::VERSION {
+            return Err(format!("unknown batch version {}", version));
+        }
+        let check = |field: &str, present: bool, expected: bool| match (present, expected) {
+            (true, false) => Err(format!("unexpected {} in a v{} batch", field, version)),
+            (false, true) => Err(format!("missing {} in a v{} batch", field, version)),
+            _ => Ok(()),
+        };
+        check(
+            "blob_versioned_hash",
+            self.blob_versioned_hash.is_some(),
+            version >= v1::VERSION,
+        )?;
+        check(
+            "skipped_l1_message_bitmap",
+            self.skipped_l1_message_bitmap.is_some(),
+            version < v3::VERSION,
+        )?;
+        check(
+            "last_block_timestamp",
+            self.last_block_timestamp.is_some(),
+            version >= v3::VERSION,
+        )?;
+        check(
+            "blob_data_proof",
+            self.blob_data_proof.is_some(),
+            version >= v3::VERSION,
+        )?;
+
+        let skipped_l1_message_bitmap = self
+            .skipped_l1_message_bitmap
+            .map(|n| n.to_vec())
+            .unwrap_or_default();
+        if skipped_l1_message_bitmap.len() % 32 != 0 {
+            return Err(format!(
+                "invalid skipped_l1_message_bitmap length {}",
+                skipped_l1_message_bitmap.len()
+            ));
+        }
+        let blob_versioned_hash = self.blob_versioned_hash.unwrap_or_default();
+        let last_block_timestamp = self.last_block_timestamp.unwrap_or_default();
+        let blob_data_proof = self.blob_data_proof.unwrap_or_default();
+
+        Ok(match version {
+            v0::VERSION => DABatch::V0(v0::DABatch {
+                version,
+                batch_index: self.batch_index,
+                l1_message_popped: self.l1_message_popped,
+                total_l1_message_popped: self.total_l1_message_popped,
+                data_hash: self.data_hash,
+                parent_batch_hash: self.parent_batch_hash,
+                skipped_l1_message_bitmap,
+            }),
+            v1::VERSION => DABatch::V1(v1::DABatch {
+                version,
+                batch_index: self.batch_index,
+                l1_message_popped: self.l1_message_popped,
+                total_l1_message_popped: self.total_l1_message_popped,
+                data_hash: self.data_hash,
+                blob_versioned_hash,
+                parent_batch_hash: self.parent_batch_hash,
+                skipped_l1_message_bitmap,
+            }),
+            v2::VERSION => DABatch::V2(v2::DABatch {
+                version,
+                batch_index: self.batch_index,
+                l1_message_popped: self.l1_message_popped,
+                total_l1_message_popped: self.total_l1_message_popped,
+                data_hash: self.data_hash,
+                blob_versioned_hash,
+                parent_batch_hash: self.parent_batch_hash,
+                skipped_l1_message_bitmap,
+            }),
+            v3::VERSION => DABatch::V3(v3::DABatch {
+                version,
+                batch_index: self.batch_index,
+                l1_message_popped: self.l1_message_popped,
+                total_l1_message_popped: self.total_l1_message_popped,
+                data_hash: self.data_hash,
+                blob_versioned_hash,
+                parent_batch_hash: self.parent_batch_hash,
+                last_block_timestamp,
+                blob_data_proof,
+            }),
+            _ => DABatch::V4(v4::DABatch {
+                version,
+                batch_index: self.batch_index,
+                l1_message_popped: self.l1_message_popped,
+                total_l1_message_popped: self.total_l1_message_popped,
+                data_hash: self.data_hash,
+                blob_versioned_hash,
+                parent_batch_hash: self.parent_batch_hash,
+                last_block_timestamp,
+                blob_data_proof,
+            }),
+        })
+    }
+}
+
+// accepts the hex string and the byte array written by the untagged form
+fn deserialize_bitmap<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Bytes>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bitmap {
+        Hex(Bytes),
+        Raw(Vec<u8>),
+    }
+    Ok(Some(match Bitmap::deserialize(deserializer)? {
+        Bitmap::Hex(n) => n,
+        Bitmap::Raw(n) => n.into(),
+    }))
+}
+
+impl Serialize for DABatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.check_version()
+            .map_err(|err| <S::Error as serde::ser::Error>::custom(format!("{:?}", err)))?;
+        DABatchRepr::new(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DABatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DABatchRepr::deserialize(deserializer)?
+            .into_batch()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializes a DABatch as the hex string of its encoded header, for use with
+/// `#[serde(with = "scroll_da_codec::da_batch_hex")]`.
+pub mod da_batch_hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(batch: &DABatch, serializer: S) -> Result<S::Ok, S::Error> {
+        batch
+            .check_version()
+            .map_err(|err| <S::Error as serde::ser::Error>::custom(format!("{:?}", err)))?;
+        Bytes::from(batch.encode()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DABatch, D::Error> {
+        let data = Bytes::deserialize(deserializer)?;
+        DABatch::from_bytes(&data).map_err(|err| serde::de::Error::custom(format!("{:?}", err)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Compact {
+        #[serde(with = "da_batch_hex")]
+        batch: DABatch,
+    }
+
+    #[test]
+    fn test_da_batch_serde() {
+        let v1 = DABatch::V1(v1::DABatch {
+            version: v1::VERSION,
+            batch_index: 7,
+            skipped_l1_message_bitmap: vec![0x11; 32],
+            ..Default::default()
+        });
+        let v2 = DABatch::V2(v2::DABatch {
+            version: v2::VERSION,
+            batch_index: 7,
+            skipped_l1_message_bitmap: vec![0x11; 32],
+            ..Default::default()
+        });
+        let v3 = DABatch::V3(v3::DABatch {
+            version: v3::VERSION,
+            batch_index: 8,
+            last_block_timestamp: 100,
+            ..Default::default()
+        });
+
+        for batch in [v1, v2.clone(), v3] {
+            let json = serde_json::to_string(&batch).unwrap();
+            assert_eq!(serde_json::from_str::<DABatch>(&json).unwrap(), batch);
+
+            let compact = Compact {
+                batch: batch.clone(),
+            };
+            let json = serde_json::to_string(&compact).unwrap();
+            assert_eq!(
+                json,
+                format!(r#"{{"batch":"0x{}"}}"#, hex::encode(batch.encode()))
+            );
+            assert_eq!(serde_json::from_str::<Compact>(&json).unwrap(), compact);
+        }
+
+        let json = serde_json::to_value(&v2).unwrap();
+        assert_eq!(
+            json["skipped_l1_message_bitmap"],
+            format!("0x{}", "11".repeat(32))
+        );
+        assert!(json.get("blob_data_proof").is_none());
+
+        // the untagged form wrote the bitmap as a byte array
+        let mut legacy = json.clone();
+        legacy["skipped_l1_message_bitmap"] = serde_json::json!(vec![0x11; 32]);
+        assert_eq!(serde_json::from_value::<DABatch>(legacy).unwrap(), v2);
+
+        let mut missing = json.clone();
+        missing
+            .as_object_mut()
+            .unwrap()
+            .remove("blob_versioned_hash");
+        assert!(serde_json::from_value::<DABatch>(missing).is_err());
+
+        let mut unexpected = json.clone();
+        unexpected["last_block_timestamp"] = serde_json::json!(1);
+        assert!(serde_json::from_value::<DABatch>(unexpected).is_err());
+
+        let mismatch = DABatch::V2(v2::DABatch {
+            version: v1::VERSION,
+            ..Default::default()
+        });
+        assert!(serde_json::to_string(&mismatch).is_err());
+    }
+}
@@ -1,12 +1,12 @@
 use std::fmt::Debug;
-use std::ops::Deref;
+use std::ops::{Deref, Range};
 
 use scroll_executor::{
     revm::primitives::{keccak256, B256},
     Context, Transaction, U256,
 };
 
-use super::BatchError;
+use super::{v1, v2, v3, v4, BatchBuildReport, BatchError, BlockHeaderInfo, RawTx};
 
 lazy_static::lazy_static! {
     static ref BLSModulus: U256 = U256::from_str_radix(
@@ -19,16 +19,22 @@ lazy_static::lazy_static! {
 #[cfg(test)]
 use crate::BatchTestError;
 use crate::{
-    calc_blob_hash, compress_scroll_batch_bytes, DataCompatibilityError, BUILDIN_TRUSTED_SETTING,
+    calc_blob_hash, compress_scroll_batch_bytes, decompress_scroll_batch_bytes,
+    DataCompatibilityError, BUILDIN_TRUSTED_SETTING,
 };
 
 #[macro_export]
 macro_rules! testdata {
     ($a:expr,$n:expr) => {
+        $crate::testdata!($a, "commit", $n)
+    };
+    ($a:expr,$kind:expr,$n:expr) => {
         include_str!(concat!(
             "../../../../../../testdata/",
             $a,
-            "-commit-",
+            "-",
+            $kind,
+            "-",
             $n,
             ".calldata"
         ))
@@ -45,6 +51,18 @@ pub(crate) fn check_chunks_size<C: ChunkTrait>(chunks: &[C], max: usize) -> Resu
     Ok(())
 }
 
+pub(crate) fn u16_be(data: &[u8]) -> u16 {
+    let mut tmp = [0_u8; 2];
+    tmp.copy_from_slice(data);
+    u16::from_be_bytes(tmp)
+}
+
+pub(crate) fn u32_be(data: &[u8]) -> u32 {
+    let mut tmp = [0_u8; 4];
+    tmp.copy_from_slice(data);
+    u32::from_be_bytes(tmp)
+}
+
 pub(crate) fn u64_be(data: &[u8]) -> u64 {
     let mut tmp = [0_u8; 8];
     tmp.copy_from_slice(data);
@@ -65,12 +83,19 @@ pub(crate) fn to_hash(data: &[u8]) -> B256 {
     hash
 }
 
+/// Each field element carries 31 bytes of payload.
+pub const MAX_BLOB_PAYLOAD_SIZE: usize = c_kzg::FIELD_ELEMENTS_PER_BLOB * 31;
+
+/// Upper bound of a decompressed blob payload, far above what the proposer
+/// builds, it only stops decompression bombs.
+pub const MAX_DECOMPRESSED_PAYLOAD_SIZE: usize = 64 * MAX_BLOB_PAYLOAD_SIZE;
+
 pub(crate) fn make_blob_canonical(blob_bytes: &[u8]) -> Result<c_kzg::Blob, BatchError> {
     // FIXME: check min_check_size
     if blob_bytes.len() > 131072 {
         // CheckCompressedDataCompatibility
     }
-    if blob_bytes.len() > 126976 {
+    if blob_bytes.len() > MAX_BLOB_PAYLOAD_SIZE {
         return Err(BatchError::OversizedBatchPayload {
             size: blob_bytes.len(),
         });
@@ -87,11 +112,70 @@ pub(crate) fn make_blob_canonical(blob_bytes: &[u8]) -> Result<c_kzg::Blob, Batc
     Ok(c_kzg::Blob::new(blob))
 }
 
+// the inverse of make_blob_canonical: drop the zero high byte of every field element
+pub(crate) fn strip_blob_canonical(blob: &[u8]) -> Result<Vec<u8>, BatchError> {
+    if blob.len() != c_kzg::BYTES_PER_BLOB {
+        return Err(BatchError::InvalidBlobPayload {
+            want_at_least: c_kzg::BYTES_PER_BLOB,
+            got: blob.len(),
+        });
+    }
+
+    let mut blob_bytes = Vec::with_capacity(c_kzg::FIELD_ELEMENTS_PER_BLOB * 31);
+    for (index, element) in blob.chunks(32).enumerate() {
+        if element[0] != 0 {
+            return Err(BatchError::InvalidBlobFieldElement { index });
+        }
+        blob_bytes.extend_from_slice(&element[1..]);
+    }
+    Ok(blob_bytes)
+}
+
+// returns the length of the (optionally EIP-2718 typed) rlp encoded transaction at the head of data
+pub(crate) fn rlp_tx_len(data: &[u8]) -> Option<usize> {
+    let type_len = match *data.first()? {
+        0..=0x7f => 1,
+        _ => 0,
+    };
+    let list = &data[type_len..];
+    let prefix = *list.first()?;
+    let (header_len, payload_len) = match prefix {
+        0xc0..=0xf7 => (1, (prefix - 0xc0) as usize),
+        0xf8..=0xff => {
+            let len_of_len = (prefix - 0xf7) as usize;
+            if list.len() < 1 + len_of_len {
+                return None;
+            }
+            let mut payload_len = 0_usize;
+            for b in &list[1..1 + len_of_len] {
+                payload_len = payload_len.checked_mul(256)?.checked_add(*b as usize)?;
+            }
+            (1 + len_of_len, payload_len)
+        }
+        _ => return None,
+    };
+    let total = type_len.checked_add(header_len)?.checked_add(payload_len)?;
+    if total > data.len() {
+        return None;
+    }
+    Some(total)
+}
+
+pub(crate) fn blob_codec_params(version: u8) -> Result<(usize, BlobPayloadCompress), BatchError> {
+    Ok(match version {
+        v1::VERSION => (v1::MAX_NUM_CHUNKS, BlobPayloadCompress::None),
+        v2::VERSION => (v2::MAX_NUM_CHUNKS, BlobPayloadCompress::Zstd),
+        v3::VERSION => (v3::MAX_NUM_CHUNKS, BlobPayloadCompress::Zstd),
+        v4::VERSION => (v4::MAX_NUM_CHUNKS, BlobPayloadCompress::ZstdV4),
+        version => return Err(BatchError::BlobNotSupported { version }),
+    })
+}
+
 #[cfg(test)]
 pub(crate) fn checked_da_batch<T: BatchTrait>(data: &str) -> Result<T, BatchTestError> {
     let bytes = hex::decode(data.trim_start_matches("0x"))?;
     let bytes = &bytes[4..];
-    let bytes = solidity_parse_bytes(32, bytes);
+    let bytes = crate::solidity_parse_bytes(32, bytes)?;
     let batch = T::from_bytes(&bytes)?;
     let encoded = batch.encode();
     if encoded != bytes {
@@ -104,13 +188,9 @@ pub(crate) fn checked_da_batch<T: BatchTrait>(data: &str) -> Result<T, BatchTest
 }
 
 #[cfg(test)]
-pub(crate) fn solidity_parse_bytes(offset: usize, slice: &[u8]) -> Vec<u8> {
-    let mut data: Vec<u8> = vec![];
-    let data_offset: usize = U256::from_be_slice(&slice[offset..offset + 32]).to();
-    let data_len: usize = U256::from_be_slice(&slice[data_offset..data_offset + 32]).to();
-    let content_offset = data_offset + 32;
-    data.extend_from_slice(&slice[content_offset..content_offset + data_len]);
-    data
+pub(crate) fn test_chunks(data: &str) -> Result<Vec<Vec<u8>>, BatchTestError> {
+    let bytes = hex::decode(data.trim().trim_start_matches("0x"))?;
+    Ok(crate::solidity_parse_array_bytes(64, &bytes[4..])?)
 }
 
 #[cfg(test)]
@@ -119,15 +199,16 @@ pub(crate) fn test_dabatch<T: BatchTrait>(testdata: &str) -> Result<(), BatchTes
     Ok(())
 }
 
-pub trait TxTrait: Sized + Debug {
+pub trait TxTrait: Sized + Debug + Clone {
     fn new(tx: &Transaction, rlp: Vec<u8>) -> Self;
+    fn from_raw(tx: RawTx) -> Self;
     fn is_l1_msg(&self) -> bool;
     fn nonce(&self) -> u64;
     fn hash(&self) -> B256;
     fn rlp_bytes(&self) -> &[u8];
 }
 
-pub trait ChunkTrait: Sized + Debug + Default {
+pub trait ChunkTrait: Sized + Debug + Default + Clone {
     type Block: BlockTrait;
     fn add_block(&mut self, blk: Self::Block);
     fn blocks(&self) -> &[Self::Block];
@@ -139,16 +220,48 @@ pub trait ChunkTrait: Sized + Debug + Default {
             .last()
             .ok_or(BatchError::TooFewBlocksInLastChunk)
     }
+
+    /// The chunk as committed in calldata. The L2 transactions follow the
+    /// block contexts in v0 and are carried in the blob since v1.
+    fn calldata(
+        &self,
+        version: u8,
+        mut total_l1_message_popped_before: u64,
+    ) -> Result<Vec<u8>, BatchError> {
+        let blocks = self.blocks();
+        if blocks.is_empty() || blocks.len() > 255 {
+            return Err(BatchError::InvalidNumBlock(blocks.len()));
+        }
+        let mut bytes = vec![blocks.len() as u8];
+        for block in blocks {
+            bytes.extend_from_slice(&block.encode(total_l1_message_popped_before)?);
+            total_l1_message_popped_before += block.num_l1_messages(total_l1_message_popped_before);
+        }
+        if version < v1::VERSION {
+            let l2_txs = blocks
+                .iter()
+                .flat_map(|blk| blk.txs())
+                .filter(|tx| !tx.is_l1_msg());
+            for tx in l2_txs {
+                bytes.extend_from_slice(&(tx.rlp_bytes().len() as u32).to_be_bytes());
+                bytes.extend_from_slice(tx.rlp_bytes());
+            }
+        }
+        Ok(bytes)
+    }
 }
 
-pub trait BlockTrait: Sized + Debug {
+pub trait BlockTrait: Sized + Debug + Clone {
     type Tx: TxTrait;
     fn new<C: Context>(ctx: &C, txs: Vec<Self::Tx>) -> Self;
+    fn from_header(header: &BlockHeaderInfo, txs: Vec<Self::Tx>) -> Self;
     fn number(&self) -> u64;
     fn timestamp(&self) -> u64;
     fn txs(&self) -> &[Self::Tx];
     fn num_l1_messages(&self, total_l1_message_popped_before: u64) -> u64;
-    fn encode(&self) -> Result<Vec<u8>, BatchError>;
+    /// The 60-byte block context. Like the Go codec, the L1 message count
+    /// covers the whole popped range, skipped messages included.
+    fn encode(&self, total_l1_message_popped_before: u64) -> Result<Vec<u8>, BatchError>;
 }
 
 pub trait BatchTrait: Sized + Debug {
@@ -156,7 +269,26 @@ pub trait BatchTrait: Sized + Debug {
     fn encode(&self) -> Vec<u8>;
 
     // func NewDABatch(batch *encoding.Batch) (*DABatch, error)
-    fn new<B: BatchTrait, C: ChunkTrait>(parent: B, chunks: Vec<C>) -> Result<Self, BatchError>;
+    fn new<B: BatchTrait, C: ChunkTrait>(parent: B, chunks: Vec<C>) -> Result<Self, BatchError> {
+        Self::new_with_settings(parent, chunks, &BUILDIN_TRUSTED_SETTING)
+    }
+
+    /// Same as new, the blob is committed with the given trusted setup.
+    fn new_with_settings<B: BatchTrait, C: ChunkTrait>(
+        parent: B,
+        chunks: Vec<C>,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<Self, BatchError> {
+        Ok(Self::new_with_report(parent, chunks, settings)?.0)
+    }
+
+    /// Same as new_with_settings, also returns the chunk hashes and the blob
+    /// payload the batch was built from.
+    fn new_with_report<B: BatchTrait, C: ChunkTrait>(
+        parent: B,
+        chunks: Vec<C>,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BatchBuildReport), BatchError>;
 
     // func NewDABatchFromBytes(data []byte) (*DABatch, error);
     fn from_bytes(data: &[u8]) -> Result<Self, BatchError>;
@@ -291,12 +423,310 @@ pub(crate) fn check_compressed_data_compatibility(
     Ok(())
 }
 
+/// L1 message queue indices popped by a batch, as consecutive ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct L1MessageQueueRanges {
+    pub skipped: Vec<Range<u64>>,
+    pub included: Vec<Range<u64>>,
+}
+
+impl L1MessageQueueRanges {
+    pub fn num_skipped(&self) -> u64 {
+        self.skipped.iter().map(|r| r.end - r.start).sum()
+    }
+
+    pub fn num_included(&self) -> u64 {
+        self.included.iter().map(|r| r.end - r.start).sum()
+    }
+}
+
+/// Reads back the bitmap written by construct_skipped_bitmap. The batch pops
+/// `l1_message_popped` messages starting at `total_l1_message_popped_before`.
+pub fn decode_skipped_bitmap(
+    version: u8,
+    bitmap: &[u8],
+    total_l1_message_popped_before: u64,
+    l1_message_popped: u64,
+) -> Result<L1MessageQueueRanges, BatchError> {
+    let num_words = l1_message_popped.div_ceil(256) as usize;
+    if bitmap.len() != num_words * 32 {
+        return Err(BatchError::InvalidSkippedBitmapLength {
+            version,
+            len: bitmap.len(),
+        });
+    }
+
+    let mut ranges = L1MessageQueueRanges::default();
+    for offset in 0..l1_message_popped {
+        let word = U256::from_be_slice(&bitmap[(offset / 256) as usize * 32..][..32]);
+        let list = match word.bit((offset % 256) as usize) {
+            true => &mut ranges.skipped,
+            false => &mut ranges.included,
+        };
+        let index = total_l1_message_popped_before + offset;
+        match list.last_mut() {
+            Some(last) if last.end == index => last.end += 1,
+            _ => list.push(index..index + 1),
+        }
+    }
+    Ok(ranges)
+}
+
+// the raw (un-padded, uncompressed) blob payload: metadata followed by the L2 txs of every chunk
+pub(crate) fn construct_batch_payload<C: ChunkTrait>(chunks: &[C], max_chunks: usize) -> Vec<u8> {
+    // metadata consists of num_chunks (2 bytes) and chunki_size (4 bytes per chunk)
+    let metadata_length = 2 + max_chunks * 4;
+    let mut blob_bytes = vec![0_u8; metadata_length];
+    write_u16(&mut blob_bytes[..], chunks.len() as u16);
+
+    for (chunk_id, chunk) in chunks.iter().enumerate() {
+        let current_chunk_start_index = blob_bytes.len();
+        for block in chunk.blocks() {
+            for tx in block.txs() {
+                if tx.is_l1_msg() {
+                    continue;
+                }
+                blob_bytes.extend_from_slice(tx.rlp_bytes());
+            }
+        }
+        let chunk_size = blob_bytes.len() - current_chunk_start_index;
+        if chunk_size != 0 {
+            write_u32(&mut blob_bytes[2 + 4 * chunk_id..], chunk_size as u32);
+        }
+    }
+    blob_bytes
+}
+
+// splits the raw blob payload into the data of each chunk
+pub(crate) fn split_batch_payload(
+    payload: &[u8],
+    max_chunks: usize,
+) -> Result<Vec<&[u8]>, BatchError> {
+    // metadata consists of num_chunks (2 bytes) and chunki_size (4 bytes per chunk)
+    let metadata_length = 2 + max_chunks * 4;
+    if payload.len() < metadata_length {
+        return Err(BatchError::InvalidBlobPayload {
+            want_at_least: metadata_length,
+            got: payload.len(),
+        });
+    }
+
+    let num_chunks = u16_be(&payload[..2]) as usize;
+    if num_chunks > max_chunks {
+        return Err(BatchError::TooManyChunksInBlob {
+            num_chunks,
+            max: max_chunks,
+        });
+    }
+
+    let mut chunks = Vec::with_capacity(num_chunks);
+    let mut offset = metadata_length;
+    for chunk_id in 0..num_chunks {
+        let chunk_size = u32_be(&payload[2 + 4 * chunk_id..][..4]) as usize;
+        let end = offset + chunk_size;
+        if end > payload.len() {
+            return Err(BatchError::InvalidBlobPayload {
+                want_at_least: end,
+                got: payload.len(),
+            });
+        }
+        chunks.push(&payload[offset..end]);
+        offset = end;
+    }
+    Ok(chunks)
+}
+
+// the challenge digest preimage: hashes of the metadata, of each chunk and the blob versioned hash
+pub(crate) fn compute_challenge_preimage(
+    payload: &[u8],
+    max_chunks: usize,
+    blob_versioned_hash: B256,
+) -> Result<Vec<u8>, BatchError> {
+    let chunks = split_batch_payload(payload, max_chunks)?;
+    let metadata_length = 2 + max_chunks * 4;
+
+    // challenge digest preimage
+    // 1 hash for metadata, 1 hash for each chunk, 1 hash for blob versioned hash
+    let mut challenge_preimage = vec![0_u8; (1 + max_chunks + 1) * 32];
+
+    // challenge: compute metadata hash
+    let hash = keccak256(&payload[..metadata_length]);
+    copy(&mut challenge_preimage[0..], &hash[..]);
+
+    // the chunk data hash used for calculating the challenge preimage
+    let mut chunk_data_hash = B256::default();
+    for chunk_id in 0..max_chunks {
+        // if we have fewer than max_chunks chunks, use the last chunk's data hash as padding
+        if let Some(chunk) = chunks.get(chunk_id) {
+            chunk_data_hash = keccak256(chunk);
+        }
+        copy(
+            &mut challenge_preimage[32 + chunk_id * 32..],
+            &chunk_data_hash[..],
+        );
+    }
+
+    // challenge: append blob versioned hash
+    copy(
+        &mut challenge_preimage[(1 + max_chunks) * 32..],
+        &blob_versioned_hash[..],
+    );
+    Ok(challenge_preimage)
+}
+
+// the challenge point z, derived from the raw blob payload and the blob versioned hash
+pub(crate) fn compute_challenge_point(
+    payload: &[u8],
+    max_chunks: usize,
+    blob_versioned_hash: B256,
+) -> Result<B256, BatchError> {
+    let challenge_preimage = compute_challenge_preimage(payload, max_chunks, blob_versioned_hash)?;
+    Ok(challenge_point(&challenge_preimage))
+}
+
+// z = keccak256(challenge_preimage) % BLS_MODULUS
+fn challenge_point(challenge_preimage: &[u8]) -> B256 {
+    let challenge_digest = keccak256(challenge_preimage);
+    let point = U256::from_be_bytes(challenge_digest.0) % *BLSModulus;
+    point.to_be_bytes().into()
+}
+
+// applies the codec compression to the raw payload, returns the bytes to put into
+// the blob and whether they are compressed
+pub(crate) fn compress_blob_payload(
+    mut blob_bytes: Vec<u8>,
+    compress: BlobPayloadCompress,
+    policy: BlobCompressPolicy,
+    num_chunks: usize,
+) -> Result<(Vec<u8>, BlobCompressOutcome), BatchError> {
+    Ok(match compress {
+        BlobPayloadCompress::None => (blob_bytes, BlobCompressOutcome::Uncompressed),
+        BlobPayloadCompress::Zstd => {
+            blob_bytes =
+                compress_scroll_batch_bytes(&blob_bytes).map_err(BatchError::ZstdEncode)?;
+            (blob_bytes, BlobCompressOutcome::Compressed)
+        }
+        BlobPayloadCompress::ZstdV4 => {
+            let outcome = match policy {
+                BlobCompressPolicy::Never => BlobCompressOutcome::Uncompressed,
+                BlobCompressPolicy::Always => {
+                    blob_bytes =
+                        compress_scroll_batch_bytes(&blob_bytes).map_err(BatchError::ZstdEncode)?;
+                    BlobCompressOutcome::Compressed
+                }
+                BlobCompressPolicy::IfCompatible | BlobCompressPolicy::RequireCompatible => {
+                    // disable compression when the data compatibility check failed (should have only 1 chunk)
+                    // https://github.com/scroll-tech/scroll/blob/ae8c858a071c84647f921d91aa8fdee320f6c433/rollup/internal/controller/watcher/batch_proposer.go#L175
+                    let compression_blob =
+                        compress_scroll_batch_bytes(&blob_bytes).map_err(BatchError::ZstdEncode)?;
+                    match check_compressed_data_compatibility(&compression_blob) {
+                        Ok(_) => {
+                            blob_bytes = compression_blob;
+                            BlobCompressOutcome::Compressed
+                        }
+                        Err(err) if policy == BlobCompressPolicy::RequireCompatible => {
+                            return Err(err.into());
+                        }
+                        Err(err) => {
+                            log::warn!(
+                                "chunk_size: {}, compressed_data_compatibility: {:?}",
+                                num_chunks,
+                                err
+                            );
+                            BlobCompressOutcome::Incompatible(err)
+                        }
+                    }
+                }
+            };
+            let compress_bit = outcome.is_compressed() as u8;
+
+            ([&[compress_bit], blob_bytes.as_slice()].concat(), outcome)
+        }
+    })
+}
+
+/// How to compress a ZstdV4 payload, whose blob carries a compress flag. The
+/// other formats are fixed by the codec version and ignore the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlobCompressPolicy {
+    /// compress without checking the compressed data
+    Always,
+    Never,
+    /// fall back to uncompressed when the compressed data fails the
+    /// compatibility check, the rule of the v4 codec
+    #[default]
+    IfCompatible,
+    /// fail with ZstdDataCompatibility when the compressed data fails the
+    /// compatibility check
+    RequireCompatible,
+}
+
+/// Whether a blob payload came out compressed, and why not.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlobCompressOutcome {
+    Compressed,
+    /// the format or the policy doesn't compress
+    Uncompressed,
+    /// fell back to uncompressed, the compressed data failed the compatibility check
+    Incompatible(DataCompatibilityError),
+}
+
+impl BlobCompressOutcome {
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::Compressed)
+    }
+
+    pub fn incompatibility(&self) -> Option<&DataCompatibilityError> {
+        match self {
+            Self::Incompatible(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Blob usage of a batch payload, computed without any KZG work.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobPayloadSize {
+    /// metadata and L2 transactions
+    pub uncompressed_size: usize,
+    /// the bytes put into the blob, including the v4 compress flag
+    pub compressed_size: usize,
+    pub num_field_elements: usize,
+    pub outcome: BlobCompressOutcome,
+}
+
+impl BlobPayloadSize {
+    pub fn fits(&self) -> bool {
+        self.compressed_size <= MAX_BLOB_PAYLOAD_SIZE
+    }
+}
+
+/// The intermediate values behind a blob, for diffing against another codec
+/// implementation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobPayloadReport {
+    /// metadata and L2 transactions
+    pub uncompressed_size: usize,
+    /// the bytes put into the blob, including the v4 compress flag
+    pub compressed_size: usize,
+    pub outcome: BlobCompressOutcome,
+    pub challenge_preimage: Vec<u8>,
+    pub blob_versioned_hash: B256,
+    pub z: B256,
+    pub y: B256,
+}
+
 pub struct BlobPayload {
     pub blob: c_kzg::Blob,
     pub blob_versioned_hash: B256,
+    /// the challenge point z and the evaluation y
     pub proof: [B256; 2],
+    pub commitment: c_kzg::KzgCommitment,
+    pub kzg_proof: c_kzg::KzgProof,
+    pub outcome: BlobCompressOutcome,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BlobPayloadCompress {
     None,
     Zstd,
@@ -304,131 +734,445 @@ pub enum BlobPayloadCompress {
 }
 
 impl BlobPayload {
+    /// Compresses with BlobCompressPolicy::IfCompatible, see outcome for the result.
     pub fn build<C: ChunkTrait>(
         chunks: &[C],
         max_chunks: usize,
         compress: BlobPayloadCompress,
     ) -> Result<Self, BatchError> {
-        // metadata consists of num_chunks (2 bytes) and chunki_size (4 bytes per chunk)
-        let metadata_length = 2 + max_chunks * 4;
+        Self::build_with_settings(chunks, max_chunks, compress, &BUILDIN_TRUSTED_SETTING)
+    }
+
+    pub fn build_with_policy<C: ChunkTrait>(
+        chunks: &[C],
+        max_chunks: usize,
+        compress: BlobPayloadCompress,
+        policy: BlobCompressPolicy,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<Self, BatchError> {
+        Ok(Self::build_inner(chunks, max_chunks, compress, policy, settings)?.0)
+    }
+
+    /// Same as build, with a trusted setup loaded through KzgSettingsSource.
+    pub fn build_with_settings<C: ChunkTrait>(
+        chunks: &[C],
+        max_chunks: usize,
+        compress: BlobPayloadCompress,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<Self, BatchError> {
+        let policy = BlobCompressPolicy::default();
+        Ok(Self::build_inner(chunks, max_chunks, compress, policy, settings)?.0)
+    }
+
+    /// Same as build, also returns the intermediate values behind the blob.
+    pub fn build_with_report<C: ChunkTrait>(
+        chunks: &[C],
+        max_chunks: usize,
+        compress: BlobPayloadCompress,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BlobPayloadReport), BatchError> {
+        let policy = BlobCompressPolicy::default();
+        Self::build_inner(chunks, max_chunks, compress, policy, settings)
+    }
 
+    fn build_inner<C: ChunkTrait>(
+        chunks: &[C],
+        max_chunks: usize,
+        compress: BlobPayloadCompress,
+        policy: BlobCompressPolicy,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BlobPayloadReport), BatchError> {
         // the raw (un-padded) blob payload
-        let mut blob_bytes = vec![0_u8; metadata_length];
+        let blob_bytes = construct_batch_payload(chunks, max_chunks);
+        let challenge_payload = blob_bytes.clone();
 
-        // challenge digest preimage
-        // 1 hash for metadata, 1 hash for each chunk, 1 hash for blob versioned hash
-        let mut challenge_preimage = vec![0_u8; (1 + max_chunks + 1) * 32];
+        let (blob_bytes, outcome) =
+            compress_blob_payload(blob_bytes, compress, policy, chunks.len())?;
+        let compressed_size = blob_bytes.len();
 
-        // the chunk data hash used for calculating the challenge preimage
-        let mut chunk_data_hash = B256::default();
+        // Only apply this check when the uncompressed batch data has exceeded 128 KiB.
+        // convert raw data to BLSFieldElements
+        let blob = make_blob_canonical(&blob_bytes)?;
 
-        // blob metadata: num_chunks
-        write_u16(&mut blob_bytes[..], chunks.len() as u16);
+        // compute blob versioned hash
+        let c = c_kzg::KzgCommitment::blob_to_kzg_commitment(&blob, settings)?;
 
-        // encode blob metadata and L2 transactions,
-        // and simultaneously also build challenge preimage
-        for (chunk_id, chunk) in chunks.into_iter().enumerate() {
-            let current_chunk_start_index = blob_bytes.len();
+        let blob_versioned_hash = calc_blob_hash(1, &c.to_bytes());
 
-            for block in chunk.blocks() {
-                for tx in block.txs() {
-                    if tx.is_l1_msg() {
-                        continue;
-                    }
+        // the challenge point z
+        let challenge_preimage =
+            compute_challenge_preimage(&challenge_payload, max_chunks, blob_versioned_hash)?;
+        let z = challenge_point(&challenge_preimage);
 
-                    // encode L2 txs into blob payload
-                    blob_bytes.extend_from_slice(&tx.rlp_bytes());
-                }
-            }
+        // the evaluation y at z
+        let (kzg_proof, y) = c_kzg::KzgProof::compute_kzg_proof(&blob, &(z.0.into()), settings)?;
+        let proof = [z, y.deref().clone().into()];
 
-            // blob metadata: chunki_size
-            let chunk_size = blob_bytes.len() - current_chunk_start_index;
-            if chunk_size != 0 {
-                write_u32(&mut blob_bytes[2 + 4 * chunk_id..], chunk_size as u32);
-            }
+        let report = BlobPayloadReport {
+            uncompressed_size: challenge_payload.len(),
+            compressed_size,
+            outcome: outcome.clone(),
+            challenge_preimage,
+            blob_versioned_hash,
+            z: proof[0],
+            y: proof[1],
+        };
+        Ok((
+            Self {
+                blob,
+                blob_versioned_hash,
+                proof,
+                commitment: c,
+                kzg_proof,
+                outcome,
+            },
+            report,
+        ))
+    }
 
-            // challenge: compute chunk data hash
-            chunk_data_hash = keccak256(&blob_bytes[current_chunk_start_index..]);
-            copy(
-                &mut challenge_preimage[32 + chunk_id * 32..],
-                &chunk_data_hash[..],
-            );
-        }
+    /// z || y || commitment || proof, the blobDataProof checked by ScrollChain
+    pub fn blob_data_proof(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(160);
+        out.extend_from_slice(self.proof[0].as_slice());
+        out.extend_from_slice(self.proof[1].as_slice());
+        out.extend_from_slice(self.commitment.to_bytes().as_slice());
+        out.extend_from_slice(self.kzg_proof.to_bytes().as_slice());
+        out
+    }
 
-        // if we have fewer than max_chunks chunks, the rest
-        // of the blob metadata is correctly initialized to 0,
-        // but we need to add padding to the challenge preimage
-        for chunk_id in chunks.len()..max_chunks {
-            // use the last chunk's data hash as padding
-            copy(
-                &mut challenge_preimage[32 + chunk_id * 32..],
-                &chunk_data_hash[..],
-            );
-        }
+    /// versioned_hash || z || y || commitment || proof, the input of the
+    /// EIP-4844 point evaluation precompile
+    pub fn point_evaluation_input(&self) -> [u8; 192] {
+        let mut out = [0_u8; 192];
+        out[..32].copy_from_slice(self.blob_versioned_hash.as_slice());
+        out[32..].copy_from_slice(&self.blob_data_proof());
+        out
+    }
+
+    pub fn verify_kzg_proof(&self, settings: &c_kzg::KzgSettings) -> Result<bool, BatchError> {
+        Ok(c_kzg::KzgProof::verify_kzg_proof(
+            &self.commitment.to_bytes(),
+            &self.proof[0].0.into(),
+            &self.proof[1].0.into(),
+            &self.kzg_proof.to_bytes(),
+            settings,
+        )?)
+    }
 
-        // challenge: compute metadata hash
-        let hash = keccak256(&blob_bytes[..metadata_length]);
-        copy(&mut challenge_preimage[0..], &hash[..]);
+    pub fn estimate_size<C: ChunkTrait>(
+        chunks: &[C],
+        max_chunks: usize,
+        compress: BlobPayloadCompress,
+    ) -> Result<BlobPayloadSize, BatchError> {
+        Self::estimate_size_with_policy(chunks, max_chunks, compress, Default::default())
+    }
+
+    pub fn estimate_size_with_policy<C: ChunkTrait>(
+        chunks: &[C],
+        max_chunks: usize,
+        compress: BlobPayloadCompress,
+        policy: BlobCompressPolicy,
+    ) -> Result<BlobPayloadSize, BatchError> {
+        let blob_bytes = construct_batch_payload(chunks, max_chunks);
+        let uncompressed_size = blob_bytes.len();
+        let (blob_bytes, outcome) =
+            compress_blob_payload(blob_bytes, compress, policy, chunks.len())?;
+        Ok(BlobPayloadSize {
+            uncompressed_size,
+            compressed_size: blob_bytes.len(),
+            num_field_elements: (blob_bytes.len() + 30) / 31,
+            outcome,
+        })
+    }
 
-        match compress {
-            BlobPayloadCompress::None => {}
-            BlobPayloadCompress::Zstd => {
-                blob_bytes =
-                    compress_scroll_batch_bytes(&blob_bytes).map_err(BatchError::ZstdEncode)?;
+    pub fn fits<C: ChunkTrait>(
+        chunks: &[C],
+        max_chunks: usize,
+        compress: BlobPayloadCompress,
+    ) -> Result<bool, BatchError> {
+        Ok(Self::estimate_size(chunks, max_chunks, compress)?.fits())
+    }
+}
+
+/// The L2 transactions carried by a blob, grouped by chunk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodedBlobPayload {
+    pub compressed: bool,
+    pub chunks: Vec<Vec<Vec<u8>>>,
+}
+
+impl BlobPayload {
+    pub fn decode(version: u8, blob: &c_kzg::Blob) -> Result<DecodedBlobPayload, BatchError> {
+        Self::decode_bytes(version, blob.deref())
+    }
+
+    pub fn decode_bytes(version: u8, blob: &[u8]) -> Result<DecodedBlobPayload, BatchError> {
+        let (max_chunks, _) = blob_codec_params(version)?;
+        let (blob_bytes, compressed) = Self::raw_payload(version, blob)?;
+
+        let mut chunks = Vec::new();
+        for (chunk_id, chunk_bytes) in split_batch_payload(&blob_bytes, max_chunks)?
+            .into_iter()
+            .enumerate()
+        {
+            let mut txs = Vec::new();
+            let mut tx_offset = 0;
+            while tx_offset < chunk_bytes.len() {
+                let tx_len =
+                    rlp_tx_len(&chunk_bytes[tx_offset..]).ok_or(BatchError::InvalidTxRlp {
+                        chunk_id,
+                        offset: tx_offset,
+                    })?;
+                txs.push(chunk_bytes[tx_offset..tx_offset + tx_len].to_vec());
+                tx_offset += tx_len;
             }
+            chunks.push(txs);
+        }
+
+        Ok(DecodedBlobPayload { compressed, chunks })
+    }
+
+    // strips the field element padding and decompresses, returns the metadata
+    // followed by the chunk data, and whether it was compressed in the blob
+    fn raw_payload(version: u8, blob: &[u8]) -> Result<(Vec<u8>, bool), BatchError> {
+        let (_, compress) = blob_codec_params(version)?;
+        let mut blob_bytes = strip_blob_canonical(blob)?;
+
+        let compressed = match compress {
+            BlobPayloadCompress::None => false,
+            BlobPayloadCompress::Zstd => true,
             BlobPayloadCompress::ZstdV4 => {
-                // disable compression when the data compatibility check failed (should have only 1 chunk)
-                // https://github.com/scroll-tech/scroll/blob/ae8c858a071c84647f921d91aa8fdee320f6c433/rollup/internal/controller/watcher/batch_proposer.go#L175
-                let compression_blob =
-                    compress_scroll_batch_bytes(&blob_bytes).map_err(BatchError::ZstdEncode)?;
-                let compress_bit = match check_compressed_data_compatibility(&compression_blob) {
-                    Ok(_) => {
-                        blob_bytes = compression_blob;
-                        1
-                    }
-                    Err(err) => {
-                        log::warn!(
-                            "chunk_size: {}, compressed_data_compatibility: {:?}",
-                            chunks.len(),
-                            err
-                        );
-                        0
-                    }
+                // the first byte tells whether the rest of the payload is compressed
+                let compressed = match blob_bytes[0] {
+                    0 => false,
+                    1 => true,
+                    flag => return Err(BatchError::InvalidBlobCompressFlag(flag)),
                 };
-
-                blob_bytes = [&[compress_bit], blob_bytes.as_slice()].concat();
+                blob_bytes.remove(0);
+                compressed
             }
+        };
+        if compressed {
+            blob_bytes = decompress_scroll_batch_bytes(&blob_bytes, MAX_DECOMPRESSED_PAYLOAD_SIZE)
+                .map_err(BatchError::ZstdDecode)?;
         }
+        Ok((blob_bytes, compressed))
+    }
 
-        // Only apply this check when the uncompressed batch data has exceeded 128 KiB.
-        // convert raw data to BLSFieldElements
-        let blob = make_blob_canonical(&blob_bytes)?;
+    /// Checks a published blob against the versioned hash and the [z, y] committed
+    /// in a batch header. The commitment and the proof come from the blob sidecar.
+    pub fn verify_blob_data_proof(
+        version: u8,
+        blob: &c_kzg::Blob,
+        commitment: &c_kzg::Bytes48,
+        proof: &c_kzg::Bytes48,
+        blob_versioned_hash: B256,
+        blob_data_proof: [B256; 2],
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(), BatchError> {
+        let got = calc_blob_hash(1, commitment);
+        if got != blob_versioned_hash {
+            return Err(BatchError::BlobVersionedHashMismatch {
+                want: blob_versioned_hash,
+                got,
+            });
+        }
 
-        // compute blob versioned hash
-        let c = c_kzg::KzgCommitment::blob_to_kzg_commitment(&blob, &BUILDIN_TRUSTED_SETTING)?;
+        let (max_chunks, _) = blob_codec_params(version)?;
+        let (payload, _) = Self::raw_payload(version, blob.deref())?;
+        let z = compute_challenge_point(&payload, max_chunks, blob_versioned_hash)?;
+        if z != blob_data_proof[0] {
+            return Err(BatchError::BlobChallengeMismatch {
+                want: blob_data_proof[0],
+                got: z,
+            });
+        }
 
-        let blob_versioned_hash = calc_blob_hash(1, &c.to_bytes());
+        let valid = c_kzg::KzgProof::verify_kzg_proof(
+            commitment,
+            &z.0.into(),
+            &blob_data_proof[1].0.into(),
+            proof,
+            settings,
+        )?;
+        if !valid {
+            return Err(BatchError::InvalidBlobKzgProof);
+        }
+        Ok(())
+    }
+}
 
-        // challenge: append blob versioned hash
-        copy(
-            &mut challenge_preimage[(1 + max_chunks) * 32..],
-            &blob_versioned_hash[..],
-        );
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v0::{DABlock, DABlockTx};
+
+    fn test_tx(l1_msg: bool, nonce: u64, rlp: Vec<u8>) -> DABlockTx {
+        DABlockTx {
+            l1_msg,
+            nonce,
+            tx_hash: keccak256(&rlp),
+            rlp,
+        }
+    }
 
-        // compute z = challenge_digest % BLS_MODULUS
-        let challenge_digest = keccak256(&challenge_preimage);
-        let point = U256::from_be_bytes(challenge_digest.0) % *BLSModulus;
-        let z: B256 = point.to_be_bytes().into();
+    #[test]
+    fn test_decode_skipped_bitmap() {
+        let chunks = vec![v1::DAChunk {
+            blocks: vec![DABlock {
+                number: 1,
+                txs: vec![
+                    test_tx(true, 5, vec![0x7e, 0xc1, 0x01]),
+                    test_tx(false, 0, vec![0xc1, 0x01]),
+                    test_tx(true, 8, vec![0x7e, 0xc1, 0x02]),
+                    test_tx(true, 300, vec![0x7e, 0xc1, 0x03]),
+                ],
+                ..Default::default()
+            }],
+        }];
+        let (bitmap, total) = construct_skipped_bitmap(1, &chunks, 3).unwrap();
+        assert_eq!(total, 301);
+
+        let ranges = decode_skipped_bitmap(1, &bitmap, 3, total - 3).unwrap();
+        assert_eq!(ranges.skipped, vec![3..5, 6..8, 9..300]);
+        assert_eq!(ranges.included, vec![5..6, 8..9, 300..301]);
+        assert_eq!(ranges.num_skipped() + ranges.num_included(), total - 3);
+
+        assert!(decode_skipped_bitmap(1, &bitmap[..32], 3, total - 3).is_err());
+    }
 
-        // the challenge point z
-        let (_, y) =
-            c_kzg::KzgProof::compute_kzg_proof(&blob, &(z.0.into()), &BUILDIN_TRUSTED_SETTING)?;
-        let proof = [z, y.deref().clone().into()];
+    #[test]
+    fn test_blob_payload_decode() {
+        let mut long_tx = vec![0x02, 0xf8, 60];
+        long_tx.extend_from_slice(&[0x11; 60]);
+        let chunks = vec![
+            v1::DAChunk {
+                blocks: vec![DABlock {
+                    number: 1,
+                    txs: vec![
+                        test_tx(true, 0, vec![0x7e, 0xc1, 0x01]),
+                        test_tx(false, 0, vec![0xc3, 0x01, 0x02, 0x03]),
+                        test_tx(false, 1, long_tx.clone()),
+                    ],
+                    ..Default::default()
+                }],
+            },
+            v1::DAChunk {
+                blocks: vec![DABlock {
+                    number: 2,
+                    txs: vec![test_tx(true, 1, vec![0x7e, 0xc1, 0x02])],
+                    ..Default::default()
+                }],
+            },
+            v1::DAChunk {
+                blocks: vec![DABlock {
+                    number: 3,
+                    txs: vec![test_tx(false, 2, vec![0x01, 0xc2, 0x05, 0x06])],
+                    ..Default::default()
+                }],
+            },
+        ];
+        let expect = vec![
+            vec![vec![0xc3, 0x01, 0x02, 0x03], long_tx],
+            vec![],
+            vec![vec![0x01, 0xc2, 0x05, 0x06]],
+        ];
+
+        for version in 1..=4 {
+            let (max_chunks, compress) = blob_codec_params(version).unwrap();
+            let payload = BlobPayload::build(&chunks, max_chunks, compress).unwrap();
+            let decoded = BlobPayload::decode(version, &payload.blob).unwrap();
+            assert_eq!(decoded.chunks, expect, "version {}", version);
+
+            let size = BlobPayload::estimate_size(&chunks, max_chunks, compress).unwrap();
+            assert!(size.fits());
+            assert_eq!(
+                size.outcome.is_compressed(),
+                decoded.compressed,
+                "version {}",
+                version
+            );
+            assert_eq!(size.outcome, payload.outcome, "version {}", version);
+
+            assert!(payload.verify_kzg_proof(&BUILDIN_TRUSTED_SETTING).unwrap());
+            let input = payload.point_evaluation_input();
+            assert_eq!(&input[..32], payload.blob_versioned_hash.as_slice());
+            assert_eq!(
+                calc_blob_hash(1, &input[96..144]),
+                payload.blob_versioned_hash
+            );
 
-        Ok(Self {
-            blob,
-            blob_versioned_hash,
-            proof,
-        })
+            let verify = |proof: [B256; 2]| {
+                BlobPayload::verify_blob_data_proof(
+                    version,
+                    &payload.blob,
+                    &payload.commitment.to_bytes(),
+                    &payload.kzg_proof.to_bytes(),
+                    payload.blob_versioned_hash,
+                    proof,
+                    &BUILDIN_TRUSTED_SETTING,
+                )
+            };
+            verify(payload.proof).unwrap();
+            assert!(verify([payload.proof[0], B256::default()]).is_err());
+            assert!(verify([B256::default(), payload.proof[1]]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let raw = vec![0_u8; 64 * 1024];
+        let compressed = compress_scroll_batch_bytes(&raw).unwrap();
+        assert!(compressed.len() < 1024);
+        assert_eq!(
+            decompress_scroll_batch_bytes(&compressed, raw.len()).unwrap(),
+            raw
+        );
+        assert!(decompress_scroll_batch_bytes(&compressed, raw.len() - 1).is_err());
+    }
+
+    #[test]
+    fn test_blob_compress_policy() {
+        // incompressible data, which may fail the compatibility check
+        let mut raw = vec![0_u8; 1024];
+        for (i, n) in raw.iter_mut().enumerate() {
+            *n = keccak256((i as u64).to_be_bytes())[0];
+        }
+        let compress =
+            |policy| compress_blob_payload(raw.clone(), BlobPayloadCompress::ZstdV4, policy, 1);
+
+        let (bytes, outcome) = compress(BlobCompressPolicy::Never).unwrap();
+        assert_eq!(outcome, BlobCompressOutcome::Uncompressed);
+        assert_eq!(bytes, [&[0], raw.as_slice()].concat());
+
+        let (bytes, outcome) = compress(BlobCompressPolicy::Always).unwrap();
+        assert_eq!(outcome, BlobCompressOutcome::Compressed);
+        assert_eq!(bytes[0], 1);
+
+        let (bytes, outcome) = compress(BlobCompressPolicy::IfCompatible).unwrap();
+        match outcome {
+            BlobCompressOutcome::Incompatible(err) => {
+                assert_eq!(bytes, [&[0], raw.as_slice()].concat());
+                assert!(matches!(
+                    compress(BlobCompressPolicy::RequireCompatible),
+                    Err(BatchError::ZstdDataCompatibility(e)) if e == err
+                ));
+            }
+            outcome => {
+                assert_eq!(outcome, BlobCompressOutcome::Compressed);
+                assert_eq!(bytes[0], 1);
+                compress(BlobCompressPolicy::RequireCompatible).unwrap();
+            }
+        }
+
+        // the policy only applies to the formats with a compress flag
+        let (_, outcome) = compress_blob_payload(
+            raw.clone(),
+            BlobPayloadCompress::Zstd,
+            BlobCompressPolicy::Never,
+            1,
+        )
+        .unwrap();
+        assert_eq!(outcome, BlobCompressOutcome::Compressed);
     }
 }
@@ -4,6 +4,7 @@ pub const VERSION: u8 = 0;
 
 pub struct CodecV0 {}
 impl BatchVersionedType for CodecV0 {
+    const VERSION: u8 = VERSION;
     type Batch = DABatch;
     type Chunk = DAChunk;
     type Block = DABlock;
@@ -28,6 +29,15 @@ impl TxTrait for DABlockTx {
         }
     }
 
+    fn from_raw(tx: RawTx) -> Self {
+        Self {
+            l1_msg: tx.l1_msg,
+            nonce: tx.queue_index,
+            tx_hash: tx.hash,
+            rlp: tx.rlp,
+        }
+    }
+
     fn is_l1_msg(&self) -> bool {
         self.l1_msg
     }
@@ -68,6 +78,17 @@ impl BlockTrait for DABlock {
         }
     }
 
+    fn from_header(header: &BlockHeaderInfo, txs: Vec<Self::Tx>) -> Self {
+        DABlock {
+            number: header.number,
+            timestamp: header.timestamp,
+            base_fee: header.base_fee,
+            gas_limit: header.gas_limit,
+            hash: B256::default(),
+            txs,
+        }
+    }
+
     fn timestamp(&self) -> u64 {
         self.timestamp
     }
@@ -93,10 +114,18 @@ impl BlockTrait for DABlock {
         }
     }
 
-    fn encode(&self) -> Result<Vec<u8>, BatchError> {
+    fn encode(&self, total_l1_message_popped_before: u64) -> Result<Vec<u8>, BatchError> {
         let mut bytes = Vec::with_capacity(60);
 
-        let num_l1_messages = self.txs.iter().filter(|n| n.is_l1_msg()).count() as u16;
+        let num_l1_messages = self.num_l1_messages(total_l1_message_popped_before);
+        if num_l1_messages > u16::MAX as u64 {
+            return Err(BatchError::NumL1TxTooLarge);
+        }
+        let num_l2_transactions = self.txs.iter().filter(|n| !n.is_l1_msg()).count() as u64;
+        let num_transactions = num_l1_messages + num_l2_transactions;
+        if num_transactions > u16::MAX as u64 {
+            return Err(BatchError::NumTxTooLarge);
+        }
 
         bytes.extend_from_slice(&self.number.to_be_bytes());
         bytes.extend_from_slice(&self.timestamp.to_be_bytes());
@@ -106,19 +135,156 @@ impl BlockTrait for DABlock {
         }
         bytes.extend_from_slice(&base_fee_bytes);
         bytes.extend_from_slice(&self.gas_limit.to_be_bytes());
-        bytes.extend_from_slice(&(self.txs.len() as u16).to_be_bytes());
-        bytes.extend_from_slice(&num_l1_messages.to_be_bytes());
+        bytes.extend_from_slice(&(num_transactions as u16).to_be_bytes());
+        bytes.extend_from_slice(&(num_l1_messages as u16).to_be_bytes());
         Ok(bytes)
     }
 }
 
+/// The 60-byte block context committed in chunk calldata.
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DABlockContext {
+    pub number: u64,
+    pub timestamp: u64,
+    pub base_fee: U256,
+    pub gas_limit: u64,
+    pub num_txs: u16,
+    pub num_l1_msgs: u16,
+}
+
+impl DABlockContext {
+    pub const SIZE: usize = 60;
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BatchError> {
+        if data.len() != Self::SIZE {
+            return Err(BatchError::InvalidBlockBytes(data.to_vec().into()));
+        }
+        Ok(Self {
+            number: u64_be(&data[0..8]),
+            timestamp: u64_be(&data[8..16]),
+            base_fee: U256::from_be_slice(&data[16..48]),
+            gas_limit: u64_be(&data[48..56]),
+            num_txs: u16_be(&data[56..58]),
+            num_l1_msgs: u16_be(&data[58..60]),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.number.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&self.base_fee.to_be_bytes::<32>());
+        bytes.extend_from_slice(&self.gas_limit.to_be_bytes());
+        bytes.extend_from_slice(&self.num_txs.to_be_bytes());
+        bytes.extend_from_slice(&self.num_l1_msgs.to_be_bytes());
+        bytes
+    }
+
+    pub fn num_l2_txs(&self) -> usize {
+        self.num_txs.saturating_sub(self.num_l1_msgs) as usize
+    }
+}
+
+/// A chunk decoded from calldata.
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DAChunkContext {
+    pub blocks: Vec<DABlockContext>,
+    /// L2 transaction RLPs of each block, empty when the codec carries them in the blob.
+    pub l2_txs: Vec<Vec<Vec<u8>>>,
+}
+
+impl DAChunkContext {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut chunk_bytes = vec![self.blocks.len() as u8];
+        for block in &self.blocks {
+            chunk_bytes.extend_from_slice(&block.encode());
+        }
+        for tx in self.l2_txs.iter().flatten() {
+            chunk_bytes.extend_from_slice(&(tx.len() as u32).to_be_bytes());
+            chunk_bytes.extend_from_slice(tx);
+        }
+        chunk_bytes
+    }
+
+    pub fn block_numbers(&self) -> Vec<u64> {
+        self.blocks.iter().map(|blk| blk.number).collect()
+    }
+
+    pub(crate) fn decode_blocks(
+        version: u8,
+        data: &[u8],
+    ) -> Result<(Vec<DABlockContext>, usize), BatchError> {
+        if data.is_empty() {
+            return Err(BatchError::InvalidDAChunkData {
+                version,
+                want_at_least: 1,
+                got: data.len(),
+            });
+        }
+        let num_blocks = data[0] as usize;
+        let end = 1 + num_blocks * DABlockContext::SIZE;
+        if data.len() < end {
+            return Err(BatchError::InvalidDAChunkData {
+                version,
+                want_at_least: end,
+                got: data.len(),
+            });
+        }
+
+        let mut blocks = Vec::with_capacity(num_blocks);
+        for block_bytes in data[1..end].chunks(DABlockContext::SIZE) {
+            blocks.push(DABlockContext::from_bytes(block_bytes)?);
+        }
+        Ok((blocks, end))
+    }
+}
+
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct DAChunk {
     pub blocks: Vec<DABlock>,
 }
 
 impl DAChunk {
-    fn encode(&self, mut total_l1_msg_poped_before: u64) -> Result<Vec<u8>, BatchError> {
+    pub fn from_bytes(data: &[u8]) -> Result<DAChunkContext, BatchError> {
+        let (blocks, mut offset) = DAChunkContext::decode_blocks(VERSION, data)?;
+
+        let mut l2_txs = Vec::with_capacity(blocks.len());
+        for block in &blocks {
+            let mut txs = Vec::with_capacity(block.num_l2_txs());
+            for _ in 0..block.num_l2_txs() {
+                if data.len() < offset + 4 {
+                    return Err(BatchError::InvalidDAChunkData {
+                        version: VERSION,
+                        want_at_least: offset + 4,
+                        got: data.len(),
+                    });
+                }
+                let tx_len = u32_be(&data[offset..offset + 4]) as usize;
+                offset += 4;
+                if data.len() - offset < tx_len {
+                    return Err(BatchError::InvalidDAChunkData {
+                        version: VERSION,
+                        want_at_least: offset + tx_len,
+                        got: data.len(),
+                    });
+                }
+                txs.push(data[offset..offset + tx_len].to_vec());
+                offset += tx_len;
+            }
+            l2_txs.push(txs);
+        }
+
+        if offset != data.len() {
+            return Err(BatchError::TrailingDAChunkData {
+                version: VERSION,
+                len: data.len() - offset,
+            });
+        }
+
+        Ok(DAChunkContext { blocks, l2_txs })
+    }
+
+    pub(crate) fn encode(&self, mut total_l1_msg_poped_before: u64) -> Result<Vec<u8>, BatchError> {
         let num_blocks = self.blocks.len();
         if num_blocks > 255 || num_blocks == 0 {
             return Err(BatchError::InvalidNumBlock(num_blocks));
@@ -127,7 +293,7 @@ impl DAChunk {
         let mut chunk_bytes = vec![num_blocks as u8];
         let mut l2_tx_data_bytes = vec![];
         for block in &self.blocks {
-            let block_bytes = block.encode()?;
+            let block_bytes = block.encode(total_l1_msg_poped_before)?;
             total_l1_msg_poped_before += block.num_l1_messages(total_l1_msg_poped_before);
             if block_bytes.len() != 60 {
                 return Err(BatchError::InvalidBlockBytes(block_bytes.into()));
@@ -211,36 +377,31 @@ pub struct DABatch {
 }
 
 impl BatchTrait for DABatch {
-    fn new<B: BatchTrait, C: ChunkTrait>(parent: B, chunks: Vec<C>) -> Result<Self, BatchError> {
-        let mut data_bytes = Vec::with_capacity(chunks.len() * 32);
+    fn new_with_report<B: BatchTrait, C: ChunkTrait>(
+        parent: B,
+        chunks: Vec<C>,
+        _settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BatchBuildReport), BatchError> {
         let batch_index = parent.batch_index() + 1;
-        let mut total_l1_message_popped_before_chunk = parent.total_l1_message_popped();
 
         // skipped L1 messages bitmap
         let (bitmap_bytes, total_l1_message_popped_after) =
-            construct_skipped_bitmap(batch_index, &chunks, total_l1_message_popped_before_chunk)?;
-
-        for chunk in chunks {
-            // build data hash
-            let da_chunk_hash = chunk
-                .hash(total_l1_message_popped_before_chunk)
-                .map_err(BatchError::BuildChunkHash())?;
-            total_l1_message_popped_before_chunk +=
-                chunk.num_l1_messages(total_l1_message_popped_before_chunk);
-            data_bytes.extend_from_slice(da_chunk_hash.as_slice());
-        }
+            construct_skipped_bitmap(batch_index, &chunks, parent.total_l1_message_popped())?;
 
-        // compute data hash
-        let data_hash = keccak256(&data_bytes);
-        Ok(Self {
+        // chunk hashes and data hash
+        let mut report = BatchBuildReport::new(VERSION, &chunks, parent.total_l1_message_popped())?;
+
+        let batch = Self {
             version: VERSION,
             batch_index,
             l1_message_popped: total_l1_message_popped_after - parent.total_l1_message_popped(),
             total_l1_message_popped: total_l1_message_popped_after,
-            data_hash,
+            data_hash: report.data_hash,
             parent_batch_hash: parent.hash(),
             skipped_l1_message_bitmap: bitmap_bytes,
-        })
+        };
+        report.batch_hash = batch.hash();
+        Ok((batch, report))
     }
 
     fn batch_index(&self) -> u64 {
@@ -275,6 +436,12 @@ impl BatchTrait for DABatch {
                 got: data.len(),
             });
         }
+        if (data.len() - 89) % 32 != 0 {
+            return Err(BatchError::InvalidSkippedBitmapLength {
+                version: VERSION,
+                len: data.len() - 89,
+            });
+        }
 
         Ok(Self {
             version: data[0],
@@ -302,4 +469,90 @@ mod test {
     fn test_v0_da_batch() {
         test_dabatch::<DABatch>(testdata!("scroll-mainnet-v0", 27589)).unwrap();
     }
+
+    #[test]
+    fn test_v0_da_chunk_from_bytes() {
+        for chunk in test_chunks(testdata!("scroll-mainnet-v0", 27589)).unwrap() {
+            let decoded = DAChunk::from_bytes(&chunk).unwrap();
+            assert_eq!(decoded.l2_txs.len(), decoded.blocks.len());
+            assert_eq!(decoded.encode(), chunk);
+        }
+    }
+
+    #[test]
+    fn test_v0_derive_blocks() {
+        use crate::{CommitBatchCall, DerivedTx};
+
+        let data = testdata!("scroll-mainnet-v0", 27589);
+        let input = hex::decode(data.trim().trim_start_matches("0x")).unwrap();
+        let call = CommitBatchCall::from_calldata(&input).unwrap();
+        let blocks = call.derive_blocks(None).unwrap();
+
+        let numbers: Vec<_> = blocks.iter().map(|blk| blk.number).collect();
+        assert_eq!(numbers, call.batch_task().unwrap().block_numbers());
+
+        let mut last_queue_index = None;
+        for tx in blocks.iter().flat_map(|blk| &blk.txs) {
+            match tx {
+                DerivedTx::L1Message { queue_index } => {
+                    assert!(last_queue_index < Some(*queue_index));
+                    last_queue_index = Some(*queue_index);
+                }
+                DerivedTx::L2(tx) => assert!(!tx.from.is_zero()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_v0_derive_skipped_l1_messages() {
+        use crate::{BatchBuilderV0, CommitBatchCall, DerivedTx};
+
+        // queue indices 1, 3 and 4 are skipped
+        let parent = DABatch::default();
+        let mut builder = BatchBuilderV0::new(vec![vec![1, 2], vec![3]]);
+        for (number, queue_indices) in [(1, vec![0, 2]), (2, vec![5]), (3, vec![6])] {
+            let header = BlockHeaderInfo {
+                number,
+                ..Default::default()
+            };
+            let txs = queue_indices
+                .into_iter()
+                .map(|n| RawTx::l1_message(n, vec![0x7e, 0xc1, n as u8]))
+                .collect();
+            builder.add_raw(&header, txs).unwrap();
+        }
+        let chunks = builder.chunks().unwrap();
+        let batch = DABatch::new(parent.clone(), chunks.clone()).unwrap();
+        assert_eq!(batch.skipped_l1_message_bitmap.len(), 32);
+
+        let mut total_l1_message_popped = 0;
+        let mut calldata = Vec::new();
+        for chunk in &chunks {
+            calldata.push(chunk.calldata(VERSION, total_l1_message_popped).unwrap());
+            total_l1_message_popped += chunk.num_l1_messages(total_l1_message_popped);
+        }
+        assert_eq!(total_l1_message_popped, 7);
+        let call = CommitBatchCall::new(
+            &crate::DABatch::V0(batch),
+            crate::DABatch::V0(parent),
+            calldata,
+            None,
+        )
+        .unwrap();
+
+        let blocks = call.derive_blocks(None).unwrap();
+        let queue_indices: Vec<Vec<u64>> = blocks
+            .iter()
+            .map(|blk| {
+                blk.txs
+                    .iter()
+                    .map(|tx| match tx {
+                        DerivedTx::L1Message { queue_index } => *queue_index,
+                        DerivedTx::L2(_) => unreachable!(),
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(queue_indices, vec![vec![0, 2], vec![5], vec![6]]);
+    }
 }
@@ -6,13 +6,16 @@ use super::v0;
 //  * DAChunk.hash: removed l2 txs
 //  * added blob_versioned_hash (89..121)
 
-const MAX_NUM_CHUNKS: usize = 15;
+pub const MAX_NUM_CHUNKS: usize = 15;
 pub const VERSION: u8 = 1;
 pub type DABlock = v0::DABlock;
 pub type DABlockTx = v0::DABlockTx;
+pub type DABlockContext = v0::DABlockContext;
+pub type DAChunkContext = v0::DAChunkContext;
 
 pub struct CodecV1 {}
 impl BatchVersionedType for CodecV1 {
+    const VERSION: u8 = VERSION;
     type Batch = DABatch;
     type Chunk = DAChunk;
     type Block = DABlock;
@@ -32,10 +35,14 @@ pub struct DABatch {
 }
 
 impl BatchTrait for DABatch {
-    fn new<B: BatchTrait, C: ChunkTrait>(parent: B, chunks: Vec<C>) -> Result<Self, BatchError> {
+    fn new_with_report<B: BatchTrait, C: ChunkTrait>(
+        parent: B,
+        chunks: Vec<C>,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BatchBuildReport), BatchError> {
         check_chunks_size(&chunks, MAX_NUM_CHUNKS)?;
 
-        let data_hash = compute_batch_data_hash(&chunks, parent.total_l1_message_popped())?;
+        let mut report = BatchBuildReport::new(VERSION, &chunks, parent.total_l1_message_popped())?;
 
         let batch_index = parent.batch_index() + 1;
 
@@ -44,21 +51,32 @@ impl BatchTrait for DABatch {
             construct_skipped_bitmap(batch_index, &chunks, parent.total_l1_message_popped())?;
 
         // blob payload
-        let BlobPayload {
-            blob_versioned_hash,
-            ..
-        } = BlobPayload::build(&chunks, MAX_NUM_CHUNKS, BlobPayloadCompress::None)?;
+        let (
+            BlobPayload {
+                blob_versioned_hash,
+                ..
+            },
+            blob_report,
+        ) = BlobPayload::build_with_report(
+            &chunks,
+            MAX_NUM_CHUNKS,
+            BlobPayloadCompress::None,
+            settings,
+        )?;
+        report.blob = Some(blob_report);
 
-        Ok(Self {
+        let batch = Self {
             version: VERSION,
             batch_index,
             l1_message_popped: total_l1_message_popped_after - parent.total_l1_message_popped(),
             total_l1_message_popped: total_l1_message_popped_after,
-            data_hash,
+            data_hash: report.data_hash,
             blob_versioned_hash,
             parent_batch_hash: parent.hash(),
             skipped_l1_message_bitmap: bitmap_bytes,
-        })
+        };
+        report.batch_hash = batch.hash();
+        Ok((batch, report))
     }
 
     fn encode(&self) -> Vec<u8> {
@@ -82,6 +100,12 @@ impl BatchTrait for DABatch {
                 got: data.len(),
             });
         }
+        if (data.len() - 121) % 32 != 0 {
+            return Err(BatchError::InvalidSkippedBitmapLength {
+                version: VERSION,
+                len: data.len() - 121,
+            });
+        }
         Ok(Self {
             version: data[0],
             batch_index: u64_be(&data[1..9]),
@@ -112,6 +136,23 @@ pub struct DAChunk {
     pub blocks: Vec<DABlock>,
 }
 
+impl DAChunk {
+    // L2 transactions moved into the blob, the calldata only carries the block contexts
+    pub fn from_bytes(data: &[u8]) -> Result<DAChunkContext, BatchError> {
+        let (blocks, offset) = DAChunkContext::decode_blocks(VERSION, data)?;
+        if offset != data.len() {
+            return Err(BatchError::TrailingDAChunkData {
+                version: VERSION,
+                len: data.len() - offset,
+            });
+        }
+        Ok(DAChunkContext {
+            blocks,
+            l2_txs: Vec::new(),
+        })
+    }
+}
+
 impl ChunkTrait for DAChunk {
     type Block = DABlock;
     fn add_block(&mut self, blk: Self::Block) {
@@ -122,10 +163,11 @@ impl ChunkTrait for DAChunk {
         &self.blocks
     }
 
-    fn hash(&self, _total_l1_msg_poped_before: u64) -> Result<B256, BatchError> {
+    fn hash(&self, mut total_l1_msg_poped_before: u64) -> Result<B256, BatchError> {
         let mut data_bytes = vec![];
         for block in &self.blocks {
-            let encoded_block = block.encode()?;
+            let encoded_block = block.encode(total_l1_msg_poped_before)?;
+            total_l1_msg_poped_before += block.num_l1_messages(total_l1_msg_poped_before);
             // only the first 58 bytes are used in the hashing process
             data_bytes.extend_from_slice(&encoded_block[..58]);
         }
@@ -152,27 +194,6 @@ impl ChunkTrait for DAChunk {
     }
 }
 
-pub(crate) fn compute_batch_data_hash<C: ChunkTrait>(
-    chunks: &[C],
-    total_l1_message_popped_before: u64,
-) -> Result<B256, BatchError> {
-    let mut data_bytes = Vec::with_capacity(chunks.len() * 32);
-    let mut total_l1_message_popped_before_chunk = total_l1_message_popped_before;
-
-    for chunk in chunks {
-        let da_chunk_hash = chunk
-            .hash(total_l1_message_popped_before_chunk)
-            .map_err(BatchError::BuildChunkHash())?;
-        total_l1_message_popped_before_chunk +=
-            chunk.num_l1_messages(total_l1_message_popped_before_chunk);
-        data_bytes.extend_from_slice(da_chunk_hash.as_slice());
-    }
-
-    let data_hash = keccak256(&data_bytes);
-
-    Ok(data_hash)
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
@@ -181,4 +202,63 @@ mod test {
     fn test_v1_da_batch() {
         test_dabatch::<DABatch>(testdata!("scroll-mainnet-v1", 175900)).unwrap();
     }
+
+    #[test]
+    fn test_v1_da_chunk_from_bytes() {
+        for chunk in test_chunks(testdata!("scroll-mainnet-v1", 175900)).unwrap() {
+            let decoded = DAChunk::from_bytes(&chunk).unwrap();
+            assert!(decoded.l2_txs.is_empty());
+            assert_eq!(decoded.encode(), chunk);
+        }
+    }
+
+    // ScrollChain counts the skipped L1 messages in numL1Messages of the block
+    // context, pops them from the queue and only hashes the included ones
+    #[test]
+    fn test_v1_skipped_l1_messages() {
+        let tx = |l1_msg: bool, nonce: u64| {
+            let rlp = vec![0xc2, l1_msg as u8, nonce as u8];
+            DABlockTx {
+                l1_msg,
+                nonce,
+                tx_hash: keccak256(&rlp),
+                rlp,
+            }
+        };
+        // queue indices 3 and 4 are skipped
+        let block = DABlock {
+            number: 1,
+            gas_limit: 10_000_000,
+            txs: vec![tx(true, 2), tx(true, 5), tx(false, 0)],
+            ..Default::default()
+        };
+        let parent = crate::DABatch::V1(DABatch {
+            version: VERSION,
+            total_l1_message_popped: 2,
+            ..Default::default()
+        });
+        let mut builder =
+            crate::BatchBuilder::with_numbers(VERSION, &parent, vec![vec![1]]).unwrap();
+        builder.add_block(block.clone()).unwrap();
+        let batch = builder.build(parent).unwrap();
+
+        let calldata = DAChunk {
+            blocks: vec![block.clone()],
+        }
+        .calldata(VERSION, 2)
+        .unwrap();
+        let context = &DAChunk::from_bytes(&calldata).unwrap().blocks[0];
+        assert_eq!((context.num_txs, context.num_l1_msgs), (5, 4));
+        assert_eq!(batch.l1_message_popped(), 4);
+        assert_eq!(batch.total_l1_message_popped(), 6);
+        let ranges = batch.l1_message_queue_ranges(2).unwrap();
+        assert_eq!(ranges.skipped, vec![3..5]);
+        assert_eq!(ranges.included, vec![2..3, 5..6]);
+
+        let mut data_bytes = calldata[1..59].to_vec();
+        data_bytes.extend_from_slice(block.txs[0].tx_hash.as_slice());
+        data_bytes.extend_from_slice(block.txs[1].tx_hash.as_slice());
+        let chunk_hash = keccak256(&data_bytes);
+        assert_eq!(batch.data_hash(), keccak256(chunk_hash.as_slice()));
+    }
 }
@@ -13,10 +13,10 @@ pub type DABlock = v1::DABlock;
 
 // DAChunk groups consecutive DABlocks with their transactions.
 pub type DAChunk = v1::DAChunk;
-pub(crate) use v1::compute_batch_data_hash;
 
 pub struct CodecV2 {}
 impl BatchVersionedType for CodecV2 {
+    const VERSION: u8 = VERSION;
     type Batch = DABatch;
     type Chunk = DAChunk;
     type Block = DABlock;
@@ -36,10 +36,14 @@ pub struct DABatch {
 }
 
 impl BatchTrait for DABatch {
-    fn new<B: BatchTrait, C: ChunkTrait>(parent: B, chunks: Vec<C>) -> Result<Self, BatchError> {
+    fn new_with_report<B: BatchTrait, C: ChunkTrait>(
+        parent: B,
+        chunks: Vec<C>,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BatchBuildReport), BatchError> {
         check_chunks_size(&chunks, MAX_NUM_CHUNKS)?;
 
-        let data_hash = compute_batch_data_hash(&chunks, parent.total_l1_message_popped())?;
+        let mut report = BatchBuildReport::new(VERSION, &chunks, parent.total_l1_message_popped())?;
 
         let batch_index = parent.batch_index() + 1;
 
@@ -48,21 +52,32 @@ impl BatchTrait for DABatch {
             construct_skipped_bitmap(batch_index, &chunks, parent.total_l1_message_popped())?;
 
         // blob payload
-        let BlobPayload {
-            blob_versioned_hash,
-            ..
-        } = BlobPayload::build(&chunks, MAX_NUM_CHUNKS, BlobPayloadCompress::Zstd)?;
-
-        Ok(Self {
+        let (
+            BlobPayload {
+                blob_versioned_hash,
+                ..
+            },
+            blob_report,
+        ) = BlobPayload::build_with_report(
+            &chunks,
+            MAX_NUM_CHUNKS,
+            BlobPayloadCompress::Zstd,
+            settings,
+        )?;
+        report.blob = Some(blob_report);
+
+        let batch = Self {
             version: VERSION,
             batch_index: parent.batch_index() + 1,
             l1_message_popped: total_l1_message_popped_after - parent.total_l1_message_popped(),
             total_l1_message_popped: total_l1_message_popped_after,
-            data_hash,
+            data_hash: report.data_hash,
             blob_versioned_hash,
             parent_batch_hash: parent.hash(),
             skipped_l1_message_bitmap: bitmap_bytes,
-        })
+        };
+        report.batch_hash = batch.hash();
+        Ok((batch, report))
     }
 
     fn encode(&self) -> Vec<u8> {
@@ -86,6 +101,12 @@ impl BatchTrait for DABatch {
                 got: data.len(),
             });
         }
+        if (data.len() - 121) % 32 != 0 {
+            return Err(BatchError::InvalidSkippedBitmapLength {
+                version: VERSION,
+                len: data.len() - 121,
+            });
+        }
         Ok(Self {
             version: data[0],
             batch_index: u64_be(&data[1..9]),
@@ -14,10 +14,10 @@ pub type DABlock = v2::DABlock;
 
 // DAChunk groups consecutive DABlocks with their transactions.
 pub type DAChunk = v2::DAChunk;
-pub(crate) use v2::compute_batch_data_hash;
 
 pub struct CodecV3 {}
 impl BatchVersionedType for CodecV3 {
+    const VERSION: u8 = VERSION;
     type Batch = DABatch;
     type Chunk = DAChunk;
     type Block = DABlock;
@@ -65,6 +65,12 @@ impl BatchTrait for DABatch {
                 got: data.len(),
             });
         }
+        if data.len() > 193 {
+            return Err(BatchError::TrailingDABatchData {
+                version: VERSION,
+                len: data.len() - 193,
+            });
+        }
 
         Ok(Self {
             version: data[0],
@@ -79,10 +85,14 @@ impl BatchTrait for DABatch {
         })
     }
 
-    fn new<B: BatchTrait, C: ChunkTrait>(parent: B, chunks: Vec<C>) -> Result<Self, BatchError> {
+    fn new_with_report<B: BatchTrait, C: ChunkTrait>(
+        parent: B,
+        chunks: Vec<C>,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BatchBuildReport), BatchError> {
         check_chunks_size(&chunks, MAX_NUM_CHUNKS)?;
 
-        let data_hash = compute_batch_data_hash(&chunks, parent.total_l1_message_popped())?;
+        let mut report = BatchBuildReport::new(VERSION, &chunks, parent.total_l1_message_popped())?;
 
         let batch_index = parent.batch_index() + 1;
         // skipped L1 messages bitmap
@@ -91,19 +101,27 @@ impl BatchTrait for DABatch {
 
         let last_block = chunks.last().unwrap().last_block()?;
 
-        let blob_payload = BlobPayload::build(&chunks, MAX_NUM_CHUNKS, BlobPayloadCompress::Zstd)?;
+        let (blob_payload, blob_report) = BlobPayload::build_with_report(
+            &chunks,
+            MAX_NUM_CHUNKS,
+            BlobPayloadCompress::Zstd,
+            settings,
+        )?;
+        report.blob = Some(blob_report);
 
-        Ok(Self {
+        let batch = Self {
             version: VERSION,
             batch_index,
             l1_message_popped: total_l1_message_popped_after - parent.total_l1_message_popped(),
             total_l1_message_popped: total_l1_message_popped_after,
-            data_hash,
+            data_hash: report.data_hash,
             blob_versioned_hash: blob_payload.blob_versioned_hash,
             parent_batch_hash: parent.hash(),
             last_block_timestamp: last_block.timestamp(),
             blob_data_proof: blob_payload.proof,
-        })
+        };
+        report.batch_hash = batch.hash();
+        Ok((batch, report))
     }
 
     fn parent_batch_hash(&self) -> B256 {
@@ -122,4 +140,199 @@ mod test {
     fn test_v3_da_batch() {
         test_dabatch::<DABatch>(testdata!("scroll-mainnet-v3", 310004)).unwrap();
     }
+
+    #[test]
+    fn test_v3_da_batch_view() {
+        let batch: DABatch = checked_da_batch(testdata!("scroll-mainnet-v3", 310004)).unwrap();
+        let view = crate::DABatch::V3(batch.clone()).view();
+        assert_eq!(view.batch_index, batch.batch_index);
+        assert_eq!(view.batch_hash, batch.hash());
+        assert_eq!(view.data_hash, batch.data_hash);
+        assert_eq!(view.blob_versioned_hash, Some(batch.blob_versioned_hash));
+        assert_eq!(view.last_block_timestamp, Some(batch.last_block_timestamp));
+        assert_eq!(view.blob_data_proof, Some(batch.blob_data_proof));
+        assert_eq!(view.skipped_l1_message_bitmap, None);
+    }
+
+    #[test]
+    fn test_commit_batch_call() {
+        use crate::{
+            CommitBatchCall, COMMIT_BATCH_SELECTOR, COMMIT_BATCH_WITH_BLOB_PROOF_SELECTOR,
+        };
+
+        assert_eq!(
+            keccak256("commitBatch(uint8,bytes,bytes[],bytes)")[..4],
+            COMMIT_BATCH_SELECTOR
+        );
+        assert_eq!(
+            keccak256("commitBatchWithBlobProof(uint8,bytes,bytes[],bytes,bytes)")[..4],
+            COMMIT_BATCH_WITH_BLOB_PROOF_SELECTOR
+        );
+
+        let data = testdata!("scroll-mainnet-v2", 300000);
+        let input = hex::decode(data.trim().trim_start_matches("0x")).unwrap();
+        let call = CommitBatchCall::from_calldata(&input).unwrap();
+        assert!(matches!(call, CommitBatchCall::CommitBatch { .. }));
+        assert_eq!(call.version(), 2);
+        assert_eq!(
+            call.batch_task().unwrap(),
+            crate::BatchTask::from_calldata(&input[4..]).unwrap()
+        );
+
+        let data = testdata!("scroll-mainnet-v3", 310004);
+        let input = hex::decode(data.trim().trim_start_matches("0x")).unwrap();
+        let call = CommitBatchCall::from_calldata(&input).unwrap();
+        assert_eq!(call.version(), VERSION);
+        assert_eq!(call.blob_data_proof().map(|n| n.len()), Some(160));
+        assert_eq!(
+            call.batch_task().unwrap(),
+            crate::BatchTask::from_calldata(&input[4..]).unwrap()
+        );
+
+        // the selector is checked before anything is decoded
+        assert!(matches!(
+            CommitBatchCall::from_calldata(&[1, 2, 3, 4, 5]),
+            Err(crate::BatchError::UnknownSelector([1, 2, 3, 4]))
+        ));
+        let mut mismatch = input.clone();
+        mismatch[..4].copy_from_slice(&COMMIT_BATCH_SELECTOR);
+        assert!(matches!(
+            CommitBatchCall::from_calldata(&mismatch),
+            Err(crate::BatchError::SelectorVersionMismatch { version: 3, .. })
+        ));
+        let mut unknown = input.clone();
+        unknown[4 + 31] = 5;
+        assert!(matches!(
+            CommitBatchCall::from_calldata(&unknown),
+            Err(crate::BatchError::SelectorVersionMismatch { version: 5, .. })
+        ));
+        let mut overflow = input.clone();
+        overflow[4 + 30] = 1;
+        assert!(matches!(
+            CommitBatchCall::from_calldata(&overflow),
+            Err(crate::BatchError::InvalidCallVersion(259))
+        ));
+    }
+
+    #[test]
+    fn test_finalize_call() {
+        use crate::FinalizeCall;
+
+        let data = testdata!("scroll-mainnet-v2", "finalize", 300000);
+        let input = hex::decode(data.trim().trim_start_matches("0x")).unwrap();
+        match FinalizeCall::from_calldata(&input).unwrap() {
+            FinalizeCall::Batch(finalize) => assert_eq!(finalize.batch.version(), 2),
+            call => panic!("unexpected call: {:?}", call),
+        }
+        // the layout follows the selector, a v2 header can't go with finalizeBatch
+        let mut mismatch = input.clone();
+        mismatch[..4].copy_from_slice(&crate::FINALIZE_BATCH_SELECTOR);
+        assert!(matches!(
+            FinalizeCall::from_calldata(&mismatch),
+            Err(crate::BatchError::SelectorVersionMismatch { version: 2, .. })
+        ));
+
+        let data = testdata!("scroll-mainnet-v3", "finalize", 310004);
+        let input = hex::decode(data.trim().trim_start_matches("0x")).unwrap();
+        let call = FinalizeCall::from_calldata(&input).unwrap();
+        assert!(matches!(call, FinalizeCall::Bundle(_)));
+        assert_eq!(call.batch().version(), VERSION);
+        assert_eq!(call.batch().batch_index(), 310004);
+        let mut mismatch = input.clone();
+        mismatch[..4].copy_from_slice(&crate::FINALIZE_BATCH_WITH_PROOF_4844_SELECTOR);
+        assert!(matches!(
+            FinalizeCall::from_calldata(&mismatch),
+            Err(crate::BatchError::FinalizeBundleRequired { version: VERSION })
+        ));
+
+        let data = testdata!("scroll-sepolia-v4", "finalize", 75450);
+        let input = hex::decode(data.trim().trim_start_matches("0x")).unwrap();
+        let call = FinalizeCall::from_calldata(&input).unwrap();
+        assert!(matches!(call, FinalizeCall::Bundle(_)));
+        assert_eq!(call.batch().batch_index(), 75450);
+    }
+
+    #[test]
+    fn test_calldata_encode() {
+        use crate::{solidity_parse_bytes, CommitBatchCall, FinalizeCall};
+        use base::prover::Poe;
+
+        let poe_of = |call: &FinalizeCall| {
+            let (prev_state_root, new_state_root, withdrawal_root) = match call {
+                FinalizeCall::Batch(n) => {
+                    (n.prev_state_root, n.new_state_root, n.new_withdrawal_root)
+                }
+                FinalizeCall::Bundle(n) => {
+                    (n.prev_state_root, n.new_state_root, n.new_withdrawal_root)
+                }
+            };
+            let mut poe = Poe::default();
+            poe.prev_state_root = prev_state_root.unwrap_or_default();
+            poe.new_state_root = new_state_root;
+            poe.withdrawal_root = withdrawal_root;
+            poe.batch_hash = call.batch().hash();
+            poe
+        };
+
+        // (commit, finalize, offset of the blobDataProof in finalize, offset of the aggrProof)
+        let cases = [
+            (
+                testdata!("scroll-mainnet-v0", 27589),
+                testdata!("scroll-mainnet-v0", "finalize", 27589),
+                None,
+                128,
+            ),
+            (
+                testdata!("scroll-mainnet-v2", 300000),
+                testdata!("scroll-mainnet-v2", "finalize", 300000),
+                Some(128),
+                160,
+            ),
+            (
+                testdata!("scroll-mainnet-v3", 310004),
+                testdata!("scroll-mainnet-v3", "finalize", 310004),
+                None,
+                96,
+            ),
+        ];
+        for (commit, finalize, blob_data_proof_offset, aggr_proof_offset) in cases {
+            let commit = hex::decode(commit.trim().trim_start_matches("0x")).unwrap();
+            let finalize = hex::decode(finalize.trim().trim_start_matches("0x")).unwrap();
+
+            let call = CommitBatchCall::from_calldata(&commit).unwrap();
+            assert_eq!(call.to_calldata(), commit);
+            let finalize_call = FinalizeCall::from_calldata(&finalize).unwrap();
+            let batch = finalize_call.batch();
+            let built = CommitBatchCall::new(
+                batch,
+                call.parent_batch_header().clone(),
+                call.chunks().to_vec(),
+                call.blob_data_proof().map(|n| n.to_vec()),
+            )
+            .unwrap();
+            assert_eq!(built.to_calldata(), commit);
+
+            let blob_data_proof =
+                blob_data_proof_offset.map(|n| solidity_parse_bytes(n, &finalize[4..]).unwrap());
+            let aggr_proof = solidity_parse_bytes(aggr_proof_offset, &finalize[4..]).unwrap();
+            let poe = poe_of(&finalize_call);
+            let built = FinalizeCall::from_poes(batch.clone(), &[poe]).unwrap();
+            assert_eq!(
+                built
+                    .to_calldata(blob_data_proof.as_deref(), Some(&aggr_proof))
+                    .unwrap(),
+                finalize
+            );
+        }
+
+        let parent = crate::DABatch::V0(Default::default());
+        let batch = crate::DABatch::V3(DABatch {
+            version: VERSION,
+            parent_batch_hash: parent.hash(),
+            ..Default::default()
+        });
+        assert!(CommitBatchCall::new(&batch, parent.clone(), vec![], None).is_err());
+        assert!(CommitBatchCall::new(&batch, batch.clone(), vec![], Some(vec![])).is_err());
+        assert!(FinalizeCall::from_poes(batch, &[]).is_err());
+    }
 }
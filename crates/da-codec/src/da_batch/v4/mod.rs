@@ -14,10 +14,10 @@ pub type DABlock = v3::DABlock;
 
 // DAChunk groups consecutive DABlocks with their transactions.
 pub type DAChunk = v3::DAChunk;
-pub(crate) use v3::compute_batch_data_hash;
 
 pub struct CodecV4 {}
 impl BatchVersionedType for CodecV4 {
+    const VERSION: u8 = VERSION;
     type Batch = DABatch;
     type Chunk = DAChunk;
     type Block = DABlock;
@@ -65,6 +65,12 @@ impl BatchTrait for DABatch {
                 got: data.len(),
             });
         }
+        if data.len() > 193 {
+            return Err(BatchError::TrailingDABatchData {
+                version: VERSION,
+                len: data.len() - 193,
+            });
+        }
 
         Ok(Self {
             version: data[0],
@@ -79,10 +85,14 @@ impl BatchTrait for DABatch {
         })
     }
 
-    fn new<B: BatchTrait, C: ChunkTrait>(parent: B, chunks: Vec<C>) -> Result<Self, BatchError> {
+    fn new_with_report<B: BatchTrait, C: ChunkTrait>(
+        parent: B,
+        chunks: Vec<C>,
+        settings: &c_kzg::KzgSettings,
+    ) -> Result<(Self, BatchBuildReport), BatchError> {
         check_chunks_size(&chunks, MAX_NUM_CHUNKS)?;
 
-        let data_hash = compute_batch_data_hash(&chunks, parent.total_l1_message_popped())?;
+        let mut report = BatchBuildReport::new(VERSION, &chunks, parent.total_l1_message_popped())?;
 
         let batch_index = parent.batch_index() + 1;
         // skipped L1 messages bitmap
@@ -91,19 +101,27 @@ impl BatchTrait for DABatch {
 
         let last_block = chunks.last().unwrap().last_block()?;
 
-        let blob_payload = BlobPayload::build(&chunks, MAX_NUM_CHUNKS, BlobPayloadCompress::ZstdV4)?;
+        let (blob_payload, blob_report) = BlobPayload::build_with_report(
+            &chunks,
+            MAX_NUM_CHUNKS,
+            BlobPayloadCompress::ZstdV4,
+            settings,
+        )?;
+        report.blob = Some(blob_report);
 
-        Ok(Self {
+        let batch = Self {
             version: VERSION,
             batch_index,
             l1_message_popped: total_l1_message_popped_after - parent.total_l1_message_popped(),
             total_l1_message_popped: total_l1_message_popped_after,
-            data_hash,
+            data_hash: report.data_hash,
             blob_versioned_hash: blob_payload.blob_versioned_hash,
             parent_batch_hash: parent.hash(),
             last_block_timestamp: last_block.timestamp(),
             blob_data_proof: blob_payload.proof,
-        })
+        };
+        report.batch_hash = batch.hash();
+        Ok((batch, report))
     }
 
     fn parent_batch_hash(&self) -> B256 {
@@ -122,4 +140,18 @@ mod test {
     fn test_v4_da_batch() {
         test_dabatch::<DABatch>(testdata!("scroll-sepolia-v4", 75595)).unwrap();
     }
+
+    #[test]
+    fn test_v4_truncated_calldata() {
+        let data = testdata!("scroll-sepolia-v4", 75595);
+        let calldata = hex::decode(data.trim().trim_start_matches("0x")).unwrap();
+        let calldata = &calldata[4..];
+        for len in 0..calldata.len() {
+            // must not panic, the tail of a truncated calldata may still be decodable
+            let _ = crate::BatchTask::from_calldata(&calldata[..len]);
+            let _ = crate::FinalizeBundle::from_calldata(&calldata[..len]);
+        }
+        assert!(crate::BatchTask::from_calldata(&calldata[..32]).is_err());
+        assert!(crate::DABatch::from_bytes(&[]).is_err());
+    }
 }
@@ -0,0 +1,151 @@
+use base::eth::primitives::U256;
+use scroll_executor::Transaction;
+
+use crate::{decode_skipped_bitmap, v0, v1, v3, BatchError, BlobPayload, CommitBatchCall};
+
+/// An L2 block rebuilt only from the data committed on L1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedBlock {
+    pub number: u64,
+    pub timestamp: u64,
+    pub base_fee: U256,
+    pub gas_limit: u64,
+    pub txs: Vec<DerivedTx>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerivedTx {
+    /// L1 only commits to the queue index, the message is in the L1MessageQueue.
+    L1Message { queue_index: u64 },
+    /// Decoded with the sender recovered.
+    L2(Box<Transaction>),
+}
+
+impl DerivedBlock {
+    pub fn num_l1_messages(&self) -> usize {
+        self.txs
+            .iter()
+            .filter(|tx| matches!(tx, DerivedTx::L1Message { .. }))
+            .count()
+    }
+}
+
+impl CommitBatchCall {
+    /// Rebuilds the L2 blocks of the batch. The L2 transactions are read from
+    /// the chunks for v0 and from the blob for later versions.
+    pub fn derive_blocks(
+        &self,
+        blob: Option<&c_kzg::Blob>,
+    ) -> Result<Vec<DerivedBlock>, BatchError> {
+        let version = self.version();
+        let mut chunks = Vec::with_capacity(self.chunks().len());
+        for chunk in self.chunks() {
+            chunks.push(match version {
+                v0::VERSION => v0::DAChunk::from_bytes(chunk)?,
+                _ => v1::DAChunk::from_bytes(chunk)?,
+            });
+        }
+
+        if version != v0::VERSION {
+            let blob = blob.ok_or(BatchError::MissingBlob { version })?;
+            let payload = BlobPayload::decode(version, blob)?;
+            if payload.chunks.len() != chunks.len() {
+                return Err(BatchError::MismatchBlobChunks {
+                    want: chunks.len(),
+                    got: payload.chunks.len(),
+                });
+            }
+            for (chunk_id, (chunk, txs)) in chunks.iter_mut().zip(payload.chunks).enumerate() {
+                chunk.l2_txs = split_block_txs(chunk_id, &chunk.blocks, txs)?;
+            }
+        }
+
+        let mut queue_indices = self.l1_message_queue_indices(&chunks)?.into_iter();
+        let mut blocks = Vec::new();
+        for chunk in chunks {
+            for (ctx, l2_txs) in chunk.blocks.into_iter().zip(chunk.l2_txs) {
+                let mut txs = Vec::with_capacity(ctx.num_txs as usize);
+                // one entry per block, counted up front
+                for queue_index in queue_indices.next().unwrap_or_default() {
+                    txs.push(DerivedTx::L1Message { queue_index });
+                }
+                for rlp in &l2_txs {
+                    let tx = decode_l2_tx(rlp).map_err(|reason| BatchError::InvalidL2Tx {
+                        number: ctx.number,
+                        index: txs.len(),
+                        reason,
+                    })?;
+                    txs.push(DerivedTx::L2(Box::new(tx)));
+                }
+                blocks.push(DerivedBlock {
+                    number: ctx.number,
+                    timestamp: ctx.timestamp,
+                    base_fee: ctx.base_fee,
+                    gas_limit: ctx.gas_limit,
+                    txs,
+                });
+            }
+        }
+        Ok(blocks)
+    }
+
+    // the queue indices of the included L1 messages of each block. A block
+    // pops num_l1_msgs messages, the skipped ones included.
+    fn l1_message_queue_indices(
+        &self,
+        chunks: &[v0::DAChunkContext],
+    ) -> Result<Vec<Vec<u64>>, BatchError> {
+        let blocks: Vec<_> = chunks.iter().flat_map(|chunk| &chunk.blocks).collect();
+        let l1_message_popped: u64 = blocks.iter().map(|blk| blk.num_l1_msgs as u64).sum();
+        let start = self.parent_batch_header().total_l1_message_popped();
+        let skipped = match self.version() {
+            // nothing is skipped since v3
+            version if version >= v3::VERSION => Vec::new(),
+            version => {
+                let bitmap = self.skipped_l1_message_bitmap();
+                decode_skipped_bitmap(version, bitmap, start, l1_message_popped)?.skipped
+            }
+        };
+
+        let mut next = start;
+        let mut indices = Vec::with_capacity(blocks.len());
+        for blk in blocks {
+            let popped = next..next + blk.num_l1_msgs as u64;
+            next = popped.end;
+            indices.push(
+                popped
+                    .filter(|index| !skipped.iter().any(|range| range.contains(index)))
+                    .collect(),
+            );
+        }
+        Ok(indices)
+    }
+}
+
+// groups the L2 transactions of a chunk by block
+fn split_block_txs(
+    chunk_id: usize,
+    blocks: &[v0::DABlockContext],
+    txs: Vec<Vec<u8>>,
+) -> Result<Vec<Vec<Vec<u8>>>, BatchError> {
+    let want: usize = blocks.iter().map(|blk| blk.num_l2_txs()).sum();
+    if txs.len() != want {
+        return Err(BatchError::MismatchL2TxCount {
+            chunk_id,
+            want,
+            got: txs.len(),
+        });
+    }
+    let mut txs = txs.into_iter();
+    Ok(blocks
+        .iter()
+        .map(|blk| txs.by_ref().take(blk.num_l2_txs()).collect())
+        .collect())
+}
+
+/// Decodes an L2 transaction as carried in DA and recovers its sender.
+pub fn decode_l2_tx(rlp: &[u8]) -> Result<Transaction, String> {
+    let mut tx: Transaction = rlp::decode(rlp).map_err(|err| format!("{:?}", err))?;
+    tx.from = tx.recover_from().map_err(|err| format!("{:?}", err))?;
+    Ok(tx)
+}
@@ -6,7 +6,22 @@ base::stack_error! {
     stack_name: BatchErrorStack,
     error: {
         UnknownBatchVersion(u8),
+        BatchVersionMismatch{ variant: u8, version: u8 },
+        EmptyBatchHeader,
         InvalidDABatchData{ version: u8, want_at_least: usize, got: usize },
+        TrailingDABatchData{ version: u8, len: usize },
+        InvalidSkippedBitmapLength{ version: u8, len: usize },
+        MissingSkippedBitmap{ version: u8 },
+        InvalidDAChunkData{ version: u8, want_at_least: usize, got: usize },
+        TrailingDAChunkData{ version: u8, len: usize },
+
+        AbiOutOfRange { offset: usize, size: usize, len: usize },
+        AbiLengthOverflow { offset: usize },
+        UnknownSelector([u8; 4]),
+        InvalidCallVersion(usize),
+        SelectorVersionMismatch { selector: [u8; 4], version: u8 },
+        FinalizeBundleRequired { version: u8 },
+        MissingPrevStateRoot { batch_index: u64 },
 
         InvalidBlockNumbers(Bytes),
         InvalidBlockBytes(Bytes),
@@ -20,13 +35,42 @@ base::stack_error! {
         NumTxTooLarge,
         OversizedBatchPayload{ size: usize },
         ZstdEncode(String),
+        ZstdDecode(String),
         KzgError(String),
 
-        UnexpectedBlock { want: (usize, usize), got: (usize, usize) },
-        UnknownBlock,
+        BlobNotSupported { version: u8 },
+        InvalidBlobFieldElement { index: usize },
+        InvalidBlobCompressFlag(u8),
+        InvalidBlobPayload { want_at_least: usize, got: usize },
+        TooManyChunksInBlob { num_chunks: usize, max: usize },
+        InvalidTxRlp { chunk_id: usize, offset: usize },
+        MissingBlobDataProof { version: u8 },
+        BlobVersionedHashMismatch { want: B256, got: B256 },
+        BlobChallengeMismatch { want: B256, got: B256 },
+        InvalidBlobKzgProof,
+        BlobSidecarNotFound(B256),
+        MissingBlob { version: u8 },
+        EmptyBlobTx,
+        InvalidSignatureV(u8),
+        MismatchBlobChunks { want: usize, got: usize },
+        MismatchL2TxCount { chunk_id: usize, want: usize, got: usize },
+        InvalidL2Tx { number: u64, index: usize, reason: String },
+
+        NonContiguousBatchIndex { want: u64, got: u64 },
+        InvalidBatchVersionTransition { batch_index: u64, parent_version: u8, version: u8 },
+        ParentBatchHashMismatch { batch_index: u64, want: B256, got: B256 },
+        TotalL1MessagePoppedMismatch { batch_index: u64, want: u64, got: u64 },
+
+        UnexpectedBlockNumber { want: u64, got: u64 },
+        OversizedBlock { number: u64 },
+        UnknownBlock(u64),
+        DuplicateBlock(u64),
+        MissingBlocks(Vec<u64>),
     },
     wrap: {
         ZstdDataCompatibility(DataCompatibilityError),
+        Io(std::io::Error),
+        Json(serde_json::Error),
     },
     stack: {
         ParseBatchTaskFromCalldata(),
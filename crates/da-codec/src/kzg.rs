@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use c_kzg::{KzgSettings, BYTES_PER_G1_POINT, BYTES_PER_G2_POINT, FIELD_ELEMENTS_PER_BLOB};
 
 /// Number of G2 points required for the kzg trusted setup.
@@ -18,56 +20,175 @@ struct TrustedSetup {
 pub fn build_setting(json_bytes: &[u8]) -> Result<KzgSettings, String> {
     let setup: TrustedSetup =
         serde_json::from_slice(json_bytes).map_err(|err| format!("{:?}", err))?;
+    load_setting(&setup.g1_lagrange, &setup.g2_monomial)
+}
 
-    if setup.g1_lagrange.len() != FIELD_ELEMENTS_PER_BLOB {
+/// Builds the setting from the c-kzg `trusted_setup.txt` format: the number of
+/// g1 and g2 points, followed by the g1 lagrange and g2 monomial points.
+pub fn build_setting_from_text(text: &str) -> Result<KzgSettings, String> {
+    let mut lines = text.split_whitespace();
+    let mut read_count = |name: &str| -> Result<usize, String> {
+        let line = lines
+            .next()
+            .ok_or_else(|| format!("missing number of {} points", name))?;
+        line.parse()
+            .map_err(|err| format!("invalid number of {} points {:?}: {}", name, line, err))
+    };
+    let num_g1 = read_count("g1")?;
+    let num_g2 = read_count("g2")?;
+
+    let g1: Vec<&str> = lines.by_ref().take(num_g1).collect();
+    let g2: Vec<&str> = lines.by_ref().take(num_g2).collect();
+    // newer setups append the g1 monomial points, which c-kzg 1.0 doesn't use
+    load_setting(&g1, &g2)
+}
+
+fn load_setting<S: AsRef<str>>(
+    g1_lagrange: &[S],
+    g2_monomial: &[S],
+) -> Result<KzgSettings, String> {
+    if g1_lagrange.len() != FIELD_ELEMENTS_PER_BLOB {
         return Err(format!(
             "Invalid number of g1 points in trusted setup. Expected {} got {}",
             FIELD_ELEMENTS_PER_BLOB,
-            setup.g1_lagrange.len(),
+            g1_lagrange.len(),
         ));
     }
-    if setup.g2_monomial.len() != NUM_G2_POINTS {
+    if g2_monomial.len() != NUM_G2_POINTS {
         return Err(format!(
             "Invalid number of g2 points in trusted setup. Expected {} got {}",
             NUM_G2_POINTS,
-            setup.g2_monomial.len(),
+            g2_monomial.len(),
         ));
     }
 
-    let g1 = {
-        let mut n = Vec::with_capacity(setup.g1_lagrange.len());
-        let mut buf = [0_u8; BYTES_PER_G1_POINT];
-        for item in &setup.g1_lagrange {
-            let item = item.trim_start_matches("0x");
-            let g1_bytes = hex::decode(item).unwrap();
-            if g1_bytes.len() != BYTES_PER_G1_POINT {
-                return Err(format!(
-                    "invalid g1 bytes={}, expected {}",
-                    item, BYTES_PER_G1_POINT
-                ));
-            }
-            buf.copy_from_slice(&g1_bytes);
-            n.push(buf);
+    let g1 = decode_points::<BYTES_PER_G1_POINT, _>("g1", g1_lagrange)?;
+    let g2 = decode_points::<BYTES_PER_G2_POINT, _>("g2", g2_monomial)?;
+
+    c_kzg::KzgSettings::load_trusted_setup(&g1, &g2).map_err(|err| err.to_string())
+}
+
+fn decode_points<const N: usize, S: AsRef<str>>(
+    name: &str,
+    items: &[S],
+) -> Result<Vec<[u8; N]>, String> {
+    let mut n = Vec::with_capacity(items.len());
+    let mut buf = [0_u8; N];
+    for item in items {
+        let item = item.as_ref().trim_start_matches("0x");
+        let bytes =
+            hex::decode(item).map_err(|err| format!("invalid {} bytes={}: {}", name, item, err))?;
+        if bytes.len() != N {
+            return Err(format!("invalid {} bytes={}, expected {}", name, item, N));
         }
-        n
-    };
-    let g2 = {
-        let mut n = Vec::with_capacity(setup.g2_monomial.len());
-        let mut buf = [0_u8; BYTES_PER_G2_POINT];
-        for item in &setup.g2_monomial {
-            let item = item.trim_start_matches("0x");
-            let g2_bytes = hex::decode(item).unwrap();
-            if g2_bytes.len() != BYTES_PER_G2_POINT {
-                return Err(format!(
-                    "invalid g2 bytes={}, expected {}",
-                    item, BYTES_PER_G2_POINT
-                ));
+        buf.copy_from_slice(&bytes);
+        n.push(buf);
+    }
+    Ok(n)
+}
+
+/// Where to load the KZG trusted setup from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum KzgSettingsSource {
+    /// The setup compiled into the binary.
+    #[default]
+    Builtin,
+    /// A JSON file with `g1_lagrange` and `g2_monomial`.
+    Json(PathBuf),
+    /// A c-kzg `trusted_setup.txt` file.
+    Text(PathBuf),
+}
+
+impl KzgSettingsSource {
+    /// Picks the format by the file extension, `.json` or anything else as text.
+    pub fn from_path<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        match path.extension() {
+            Some(ext) if ext == "json" => Self::Json(path),
+            _ => Self::Text(path),
+        }
+    }
+
+    pub fn load(&self) -> Result<KzgSettings, String> {
+        let read = |path: &PathBuf| {
+            std::fs::read(path).map_err(|err| format!("read {}: {}", path.display(), err))
+        };
+        match self {
+            Self::Builtin => build_setting(BUILDIN_TRUSTED_SETUP),
+            Self::Json(path) => build_setting(&read(path)?),
+            Self::Text(path) => {
+                let data = read(path)?;
+                let text = std::str::from_utf8(&data)
+                    .map_err(|err| format!("read {}: {}", path.display(), err))?;
+                build_setting_from_text(text)
             }
-            buf.copy_from_slice(&g2_bytes);
-            n.push(buf);
         }
-        n
-    };
+    }
+}
 
-    c_kzg::KzgSettings::load_trusted_setup(&g1, &g2).map_err(|err| err.to_string())
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn builtin_text() -> String {
+        let setup: TrustedSetup = serde_json::from_slice(BUILDIN_TRUSTED_SETUP).unwrap();
+        let mut text = format!("{}\n{}\n", setup.g1_lagrange.len(), setup.g2_monomial.len());
+        for point in setup.g1_lagrange.iter().chain(&setup.g2_monomial) {
+            text.push_str(point.trim_start_matches("0x"));
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn test_build_setting_from_text() {
+        let text = builtin_text();
+        build_setting_from_text(&text).unwrap();
+
+        let truncated = text.lines().take(100).collect::<Vec<_>>().join("\n");
+        assert!(build_setting_from_text(&truncated).is_err());
+        assert!(build_setting_from_text(&text.replacen("a", "x", 1)).is_err());
+    }
+
+    #[test]
+    fn test_settings_source_load() {
+        // the same setup in every format, checked by committing to the same blob
+        let blob = c_kzg::Blob::from_bytes(&[1_u8; c_kzg::BYTES_PER_BLOB]).unwrap();
+        let want = c_kzg::KzgCommitment::blob_to_kzg_commitment(&blob, &BUILDIN_TRUSTED_SETTING)
+            .unwrap()
+            .to_bytes();
+        let commit = |source: &KzgSettingsSource| {
+            let settings = source.load().unwrap();
+            c_kzg::KzgCommitment::blob_to_kzg_commitment(&blob, &settings)
+                .unwrap()
+                .to_bytes()
+        };
+
+        let dir = std::env::temp_dir().join(format!("kzg-settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("trusted_setup.json");
+        let text = dir.join("trusted_setup.txt");
+        std::fs::write(&json, BUILDIN_TRUSTED_SETUP).unwrap();
+        std::fs::write(&text, builtin_text()).unwrap();
+
+        assert_eq!(
+            KzgSettingsSource::from_path(&json),
+            KzgSettingsSource::Json(json.clone())
+        );
+        assert_eq!(
+            KzgSettingsSource::from_path(&text),
+            KzgSettingsSource::Text(text.clone())
+        );
+        assert_eq!(commit(&KzgSettingsSource::Builtin), want);
+        assert_eq!(commit(&KzgSettingsSource::from_path(&json)), want);
+        assert_eq!(commit(&KzgSettingsSource::from_path(&text)), want);
+
+        // a file in the wrong format or a missing one
+        assert!(KzgSettingsSource::Text(json.clone()).load().is_err());
+        assert!(KzgSettingsSource::Json(text.clone()).load().is_err());
+        assert!(KzgSettingsSource::Json(dir.join("missing.json"))
+            .load()
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
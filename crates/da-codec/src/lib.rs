@@ -4,6 +4,15 @@ pub use batch_task::*;
 mod da_batch;
 pub use da_batch::*;
 
+mod blob_sidecar;
+pub use blob_sidecar::*;
+
+mod blob_tx;
+pub use blob_tx::*;
+
+mod derive;
+pub use derive::*;
+
 mod batch_chunk;
 pub use batch_chunk::*;
 
@@ -1,29 +1,131 @@
 use core::hash::{Hash, Hasher};
-use base::eth::primitives::{B256, U256};
+use base::eth::primitives::B256;
 use scroll_zstd_encoder::{init_zstd_encoder, zstd::zstd_safe::WriteBuf, N_BLOCK_SIZE_TARGET};
 
-pub(crate) fn solidity_parse_bytes(offset: usize, slice: &[u8]) -> Vec<u8> {
-    let mut data: Vec<u8> = vec![];
-    let data_offset: usize = U256::from_be_slice(&slice[offset..offset + 32]).to();
-    let data_len: usize = U256::from_be_slice(&slice[data_offset..data_offset + 32]).to();
+use crate::BatchError;
+
+const ZSTD_MAGIC_NUMBER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+pub(crate) fn split_selector(input: &[u8]) -> Result<([u8; 4], &[u8]), BatchError> {
+    if input.len() < 4 {
+        return Err(BatchError::AbiOutOfRange {
+            offset: 0,
+            size: 4,
+            len: input.len(),
+        });
+    }
+    let mut selector = [0_u8; 4];
+    selector.copy_from_slice(&input[..4]);
+    Ok((selector, &input[4..]))
+}
+
+pub(crate) fn solidity_parse_word(offset: usize, slice: &[u8]) -> Result<&[u8], BatchError> {
+    match offset.checked_add(32) {
+        Some(end) if end <= slice.len() => Ok(&slice[offset..end]),
+        _ => Err(BatchError::AbiOutOfRange {
+            offset,
+            size: 32,
+            len: slice.len(),
+        }),
+    }
+}
+
+pub(crate) fn solidity_parse_usize(offset: usize, slice: &[u8]) -> Result<usize, BatchError> {
+    let word = solidity_parse_word(offset, slice)?;
+    if word[..24].iter().any(|b| *b != 0) {
+        return Err(BatchError::AbiLengthOverflow { offset });
+    }
+    let mut tmp = [0_u8; 8];
+    tmp.copy_from_slice(&word[24..]);
+    usize::try_from(u64::from_be_bytes(tmp))
+        .map_err(|_| BatchError::AbiLengthOverflow { offset })
+}
+
+pub(crate) fn solidity_parse_bytes(offset: usize, slice: &[u8]) -> Result<Vec<u8>, BatchError> {
+    let data_offset = solidity_parse_usize(offset, slice)?;
+    let data_len = solidity_parse_usize(data_offset, slice)?;
     let content_offset = data_offset + 32;
-    data.extend_from_slice(&slice[content_offset..content_offset + data_len]);
-    data
+    match content_offset.checked_add(data_len) {
+        Some(end) if end <= slice.len() => Ok(slice[content_offset..end].to_vec()),
+        _ => Err(BatchError::AbiOutOfRange {
+            offset: content_offset,
+            size: data_len,
+            len: slice.len(),
+        }),
+    }
 }
 
-pub(crate) fn solidity_parse_array_bytes(offset: usize, slice: &[u8]) -> Vec<Vec<u8>> {
-    let len_offset: usize = U256::from_be_slice(&slice[offset..offset + 32]).to();
-    let len: usize = U256::from_be_slice(&slice[len_offset..len_offset + 32]).to();
+pub(crate) fn solidity_parse_array_bytes(
+    offset: usize,
+    slice: &[u8],
+) -> Result<Vec<Vec<u8>>, BatchError> {
+    let len_offset = solidity_parse_usize(offset, slice)?;
+    let len = solidity_parse_usize(len_offset, slice)?;
 
     let tail_offset = len_offset + 32;
     let tail = &slice[tail_offset..];
 
     let mut vs = vec![];
     for i in 0..len {
-        let data = solidity_parse_bytes(i * 32, tail);
+        let head_offset = i.checked_mul(32).ok_or(BatchError::AbiLengthOverflow {
+            offset: len_offset,
+        })?;
+        let data = solidity_parse_bytes(head_offset, tail)?;
         vs.push(data);
     }
-    vs
+    Ok(vs)
+}
+
+/// An argument of an ABI encoded call, see solidity_encode.
+pub(crate) enum SolidityValue<'a> {
+    Word(B256),
+    Bytes(&'a [u8]),
+    ArrayBytes(&'a [Vec<u8>]),
+}
+
+pub(crate) fn solidity_word_u64(n: u64) -> B256 {
+    let mut word = B256::default();
+    word.0[24..].copy_from_slice(&n.to_be_bytes());
+    word
+}
+
+// the inverse of the solidity_parse_* helpers: the head words, then the
+// dynamic arguments in order
+pub(crate) fn solidity_encode(selector: [u8; 4], args: &[SolidityValue]) -> Vec<u8> {
+    let head_size = args.len() * 32;
+    let mut head = Vec::with_capacity(head_size);
+    let mut tail = Vec::new();
+    for arg in args {
+        let word = match arg {
+            SolidityValue::Word(word) => *word,
+            SolidityValue::Bytes(data) => {
+                let offset = solidity_word_u64((head_size + tail.len()) as u64);
+                solidity_append_bytes(&mut tail, data);
+                offset
+            }
+            SolidityValue::ArrayBytes(items) => {
+                let offset = solidity_word_u64((head_size + tail.len()) as u64);
+                tail.extend_from_slice(solidity_word_u64(items.len() as u64).as_slice());
+                let mut items_tail = Vec::new();
+                for item in items.iter() {
+                    let item_offset = items.len() * 32 + items_tail.len();
+                    tail.extend_from_slice(solidity_word_u64(item_offset as u64).as_slice());
+                    solidity_append_bytes(&mut items_tail, item);
+                }
+                tail.extend_from_slice(&items_tail);
+                offset
+            }
+        };
+        head.extend_from_slice(word.as_slice());
+    }
+    [&selector[..], &head, &tail].concat()
+}
+
+// length prefixed and zero padded to whole words
+fn solidity_append_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(solidity_word_u64(data.len() as u64).as_slice());
+    out.extend_from_slice(data);
+    out.resize(out.len() + (32 - data.len() % 32) % 32, 0);
 }
 
 pub(crate) fn decode_block_numbers(mut data: &[u8]) -> Option<Vec<u64>> {
@@ -60,6 +162,28 @@ pub fn compress_scroll_batch_bytes(src: &[u8]) -> Result<Vec<u8>, String> {
     Ok(ret)
 }
 
+/// Fails once the output exceeds `limit` bytes, the input comes from the host
+/// and a small frame can expand to gigabytes.
+pub fn decompress_scroll_batch_bytes(src: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    use std::io::Read;
+    use scroll_zstd_encoder::zstd::stream::read::Decoder;
+
+    // the scroll encoder omits the magic number, add it back before decoding
+    let framed = [&ZSTD_MAGIC_NUMBER[..], src].concat();
+    let decoder = Decoder::with_buffer(framed.as_slice())
+        .map_err(|err| format!("{:?}", err))?
+        .single_frame();
+    let mut out = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|err| format!("{:?}", err))?;
+    if out.len() > limit {
+        return Err(format!("decompressed payload exceeds {} bytes", limit));
+    }
+    Ok(out)
+}
+
 pub(crate) fn sha256(buf: &[u8]) -> B256 {
     let mut out = B256::default();
     unsafe { blst::blst_sha256(out.0.as_mut_ptr(), buf.as_ptr(), buf.len()) };
@@ -18,6 +18,13 @@ impl Keypair {
         }
     }
 
+    pub fn from_secret_key(sk: SecretKey) -> Self {
+        let pk = sk.public_key(SECP256K1);
+        Self {
+            key: Arc::new(Mutex::new((None, Arc::new(sk), Arc::new(pk)))),
+        }
+    }
+
     pub fn address(&self) -> Address {
         Self::public_key_to_address(&self.public_key())
     }
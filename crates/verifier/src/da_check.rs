@@ -0,0 +1,348 @@
+use scroll_da_codec::{DerivedBlock, DerivedTx};
+use scroll_executor::{Context, Transaction, B256, U256};
+
+use crate::PobContext;
+
+/// What differs between the data committed on L1 and the traced block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DaMismatchKind {
+    NumBlocks {
+        committed: usize,
+        traced: usize,
+    },
+    Number {
+        committed: u64,
+        traced: u64,
+    },
+    Timestamp {
+        committed: u64,
+        traced: u64,
+    },
+    BaseFee {
+        committed: U256,
+        traced: U256,
+    },
+    GasLimit {
+        committed: u64,
+        traced: u64,
+    },
+    NumTxs {
+        committed: usize,
+        traced: usize,
+    },
+    /// The queue index when the transaction is an L1 message.
+    L1Message {
+        committed: Option<u64>,
+        traced: Option<u64>,
+    },
+    TxHash {
+        committed: B256,
+        traced: B256,
+    },
+}
+
+/// The first place where the traced blocks diverge from L1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaMismatch {
+    /// index of the block in the batch
+    pub block_index: usize,
+    /// None when the mismatch is in the block header
+    pub tx_index: Option<usize>,
+    pub kind: DaMismatchKind,
+}
+
+impl std::fmt::Display for DaMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DA mismatch at block[{}]", self.block_index)?;
+        if let Some(tx_index) = self.tx_index {
+            write!(f, ".tx[{}]", tx_index)?;
+        }
+        write!(f, ": {:?}", self.kind)
+    }
+}
+
+/// The fields of a traced block that are committed on L1.
+#[derive(Debug, Clone, Copy)]
+pub struct TracedBlock<'a> {
+    pub number: u64,
+    pub timestamp: u64,
+    pub base_fee: U256,
+    pub gas_limit: u64,
+    pub txs: &'a [Transaction],
+}
+
+impl<'a> From<&'a PobContext> for TracedBlock<'a> {
+    fn from(ctx: &'a PobContext) -> Self {
+        Self {
+            number: ctx.number(),
+            timestamp: ctx.timestamp().to(),
+            base_fee: ctx.base_fee_per_gas().unwrap_or_default(),
+            gas_limit: ctx.gas_limit().to(),
+            txs: ctx.txs(),
+        }
+    }
+}
+
+/// Checks the traced blocks against the blocks derived from the commit
+/// transaction, transaction by transaction.
+pub fn check_committed_blocks(
+    committed: &[DerivedBlock],
+    traced: &[PobContext],
+) -> Result<(), DaMismatch> {
+    let traced: Vec<TracedBlock> = traced.iter().map(Into::into).collect();
+    check_traced_blocks(committed, &traced)
+}
+
+/// Same as check_committed_blocks, on the committed fields of the traced blocks.
+pub fn check_traced_blocks(
+    committed: &[DerivedBlock],
+    traced: &[TracedBlock],
+) -> Result<(), DaMismatch> {
+    for (block_index, (blk, traced_blk)) in committed.iter().zip(traced).enumerate() {
+        let mismatch = |tx_index, kind| DaMismatch {
+            block_index,
+            tx_index,
+            kind,
+        };
+        if blk.number != traced_blk.number {
+            return Err(mismatch(
+                None,
+                DaMismatchKind::Number {
+                    committed: blk.number,
+                    traced: traced_blk.number,
+                },
+            ));
+        }
+        if blk.timestamp != traced_blk.timestamp {
+            return Err(mismatch(
+                None,
+                DaMismatchKind::Timestamp {
+                    committed: blk.timestamp,
+                    traced: traced_blk.timestamp,
+                },
+            ));
+        }
+        if blk.base_fee != traced_blk.base_fee {
+            return Err(mismatch(
+                None,
+                DaMismatchKind::BaseFee {
+                    committed: blk.base_fee,
+                    traced: traced_blk.base_fee,
+                },
+            ));
+        }
+        if blk.gas_limit != traced_blk.gas_limit {
+            return Err(mismatch(
+                None,
+                DaMismatchKind::GasLimit {
+                    committed: blk.gas_limit,
+                    traced: traced_blk.gas_limit,
+                },
+            ));
+        }
+
+        let traced_txs = traced_blk.txs;
+        for (tx_index, (tx, traced_tx)) in blk.txs.iter().zip(traced_txs).enumerate() {
+            let traced_l1 = match traced_tx.transaction_type.map(|n| n.as_u64()) {
+                Some(0x7E) => Some(traced_tx.nonce.as_u64()),
+                _ => None,
+            };
+            match tx {
+                DerivedTx::L1Message { queue_index } => {
+                    if traced_l1 != Some(*queue_index) {
+                        return Err(mismatch(
+                            Some(tx_index),
+                            DaMismatchKind::L1Message {
+                                committed: Some(*queue_index),
+                                traced: traced_l1,
+                            },
+                        ));
+                    }
+                }
+                DerivedTx::L2(tx) => {
+                    if traced_l1.is_some() {
+                        return Err(mismatch(
+                            Some(tx_index),
+                            DaMismatchKind::L1Message {
+                                committed: None,
+                                traced: traced_l1,
+                            },
+                        ));
+                    }
+                    if tx.hash != traced_tx.hash {
+                        return Err(mismatch(
+                            Some(tx_index),
+                            DaMismatchKind::TxHash {
+                                committed: tx.hash.0.into(),
+                                traced: traced_tx.hash.0.into(),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+        if blk.txs.len() != traced_txs.len() {
+            return Err(mismatch(
+                None,
+                DaMismatchKind::NumTxs {
+                    committed: blk.txs.len(),
+                    traced: traced_txs.len(),
+                },
+            ));
+        }
+    }
+
+    if committed.len() != traced.len() {
+        return Err(DaMismatch {
+            block_index: committed.len().min(traced.len()),
+            tx_index: None,
+            kind: DaMismatchKind::NumBlocks {
+                committed: committed.len(),
+                traced: traced.len(),
+            },
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn l1_tx(queue_index: u64) -> Transaction {
+        Transaction {
+            transaction_type: Some(0x7E_u64.into()),
+            nonce: queue_index.into(),
+            ..Default::default()
+        }
+    }
+
+    fn l2_tx(n: u8) -> Transaction {
+        Transaction {
+            hash: [n; 32].into(),
+            ..Default::default()
+        }
+    }
+
+    // queue index 1 is skipped, block 1 pops 0..3 and includes 0 and 2
+    fn blocks() -> Vec<(u64, Vec<Transaction>)> {
+        vec![
+            (1, vec![l1_tx(0), l1_tx(2), l2_tx(1)]),
+            (2, vec![l2_tx(2), l2_tx(3)]),
+        ]
+    }
+
+    fn derive(blocks: &[(u64, Vec<Transaction>)]) -> Vec<DerivedBlock> {
+        blocks
+            .iter()
+            .map(|(number, txs)| DerivedBlock {
+                number: *number,
+                timestamp: 1000 + number,
+                base_fee: U256::from(7),
+                gas_limit: 10_000_000,
+                txs: txs
+                    .iter()
+                    .map(|tx| match tx.transaction_type {
+                        Some(_) => DerivedTx::L1Message {
+                            queue_index: tx.nonce.as_u64(),
+                        },
+                        None => DerivedTx::L2(Box::new(tx.clone())),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn trace(blocks: &[(u64, Vec<Transaction>)]) -> Vec<TracedBlock<'_>> {
+        blocks
+            .iter()
+            .map(|(number, txs)| TracedBlock {
+                number: *number,
+                timestamp: 1000 + number,
+                base_fee: U256::from(7),
+                gas_limit: 10_000_000,
+                txs,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check_matching_blocks() {
+        let blocks = blocks();
+        assert_eq!(
+            check_traced_blocks(&derive(&blocks), &trace(&blocks)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_check_swapped_txs() {
+        let committed = derive(&blocks());
+        let mut traced = blocks();
+        traced[1].1.swap(0, 1);
+        assert_eq!(
+            check_traced_blocks(&committed, &trace(&traced)),
+            Err(DaMismatch {
+                block_index: 1,
+                tx_index: Some(0),
+                kind: DaMismatchKind::TxHash {
+                    committed: B256::repeat_byte(2),
+                    traced: B256::repeat_byte(3),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_num_blocks() {
+        let committed = derive(&blocks());
+
+        let mut traced = blocks();
+        traced.pop();
+        assert_eq!(
+            check_traced_blocks(&committed, &trace(&traced)),
+            Err(DaMismatch {
+                block_index: 1,
+                tx_index: None,
+                kind: DaMismatchKind::NumBlocks {
+                    committed: 2,
+                    traced: 1,
+                },
+            })
+        );
+
+        let mut traced = blocks();
+        traced.push((3, vec![l2_tx(4)]));
+        assert_eq!(
+            check_traced_blocks(&committed, &trace(&traced)),
+            Err(DaMismatch {
+                block_index: 2,
+                tx_index: None,
+                kind: DaMismatchKind::NumBlocks {
+                    committed: 2,
+                    traced: 3,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_skipped_l1_message() {
+        let committed = derive(&blocks());
+
+        // the trace includes the message skipped on L1
+        let mut traced = blocks();
+        traced[0].1.insert(1, l1_tx(1));
+        assert_eq!(
+            check_traced_blocks(&committed, &trace(&traced)),
+            Err(DaMismatch {
+                block_index: 0,
+                tx_index: Some(1),
+                kind: DaMismatchKind::L1Message {
+                    committed: Some(2),
+                    traced: Some(1),
+                },
+            })
+        );
+    }
+}
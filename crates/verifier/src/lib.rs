@@ -13,5 +13,8 @@ pub use block_trace::*;
 mod verifier;
 pub use verifier::*;
 
+mod da_check;
+pub use da_check::*;
+
 mod execution_node;
 pub use execution_node::*;
\ No newline at end of file
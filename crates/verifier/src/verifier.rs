@@ -3,10 +3,13 @@ use std::time::{Duration, Instant};
 use base::eth::{primitives::B256, EthError};
 use base::prover::{Pob, Poe};
 use base::{thread::parallel, trace::Alive};
-use scroll_da_codec::{BatchError, BatchTask};
+use scroll_da_codec::{BatchError, BatchTask, CommitBatchCall};
 use scroll_executor::{Context, ExecutionError, ExecutionResult, ScrollEvmExecutor};
 
-use crate::{block_trace_to_pob, HardforkConfig, PobContext, ScrollExecutionNode};
+use crate::{
+    block_trace_to_pob, check_committed_blocks, DaMismatch, HardforkConfig, PobContext,
+    ScrollExecutionNode,
+};
 
 #[derive(Clone)]
 pub struct ScrollBatchVerifier {
@@ -118,6 +121,19 @@ impl ScrollBatchVerifier {
         Ok(poe)
     }
 
+    /// Same as verify, but first diffs the traced blocks against the chunks and
+    /// blob committed on L1, so a tampered trace fails at the first differing
+    /// transaction. The blob is required since v1.
+    pub async fn verify_with_da(
+        call: &CommitBatchCall,
+        blob: Option<&c_kzg::Blob>,
+        ctx_list: Vec<PobContext>,
+    ) -> Result<Poe, ValidateError> {
+        let blocks = call.derive_blocks(blob)?;
+        check_committed_blocks(&blocks, &ctx_list).map_err(ValidateError::DaMismatch)?;
+        Self::verify(&call.batch_task()?, ctx_list).await
+    }
+
     fn verify_result<C: Context>(
         result: ExecutionResult,
         ctx: &C,
@@ -148,6 +164,7 @@ base::stack_error! {
         RequireExecutionEndpoint,
         FailGenPob,
         MissingBatch,
+        DaMismatch(DaMismatch),
     },
     wrap: {
         Execution(ExecutionError),